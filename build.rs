@@ -1,44 +1,65 @@
-use std::fs;
+use std::path::Path;
+use std::process::Command;
 
+use serde::Serialize;
+
+include!("src/build_naming.rs");
+include!("src/registered_plugins.rs");
+
+/// Version of the `execute` interface between the host and the computer, bump on breaking changes
+const ABI_VERSION: u32 = 1;
+
+/// Written next to every artifact in `dist/`, `build.sh` reads the name from the second line
+#[derive(Serialize)]
+struct Manifest<'a> {
+    name: &'a str,
+    version: &'a str,
+    git_commit: &'a str,
+    build_time: u64,
+    plugins: &'a [&'a str],
+    abi_version: u32,
+}
+
+fn git_commit() -> String {
+    Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_else(|| "unknown".into())
+}
 
 fn main() {
-    let time_mins = std::time::SystemTime::now()
+    let build_time = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
-        .as_secs()/60;
+        .as_secs();
 
     let cargo_toml = std::fs::read_to_string("Cargo.toml").unwrap();
 
-    let computers_dir = std::path::Path::new("./dist");
+    let computers_dir = Path::new("./dist");
     std::fs::create_dir_all(computers_dir).unwrap();
 
     let computer_builds = std::fs::read_dir(computers_dir).unwrap();
 
-    let computer_builds = computer_builds
-        .map(|x| x.unwrap().path())
-        .filter(|x| !x.is_dir())
-        .map(|x| x.file_name().unwrap().to_str().unwrap().to_string().split('.')
-            .next().unwrap().to_string())
-        .collect::<Vec<String>>();
-    
-    let mut computer_builds_versions = computer_builds
-        .iter()
-        .map(|x| {
-            x.split("_").last().unwrap().parse::<u32>().unwrap()
-        })
-        .collect::<Vec<u32>>();
-
-    computer_builds_versions.sort();
-
-    let latest_version = computer_builds_versions.last().unwrap_or(&0);
-
-    let new_version = latest_version+1;
+    let latest_version = computer_builds
+        .filter_map(|x| x.ok())
+        .filter(|x| x.path().is_file())
+        .filter_map(|x| build_number(&x.file_name().to_string_lossy()))
+        .max()
+        .unwrap_or(0);
 
     let mut lines: Vec<String> = cargo_toml.lines().map(|x| x.to_string()).collect();
     let lib_pos = lines.iter().position(|x| x.contains("[lib]")).unwrap();
     let name_pos = lines.iter().skip(lib_pos).position(|x| x.starts_with("name")).unwrap();
 
-    lines[lib_pos+name_pos] = format!("name = \"computer_{}\"", new_version);
+    // This build is compiled under the name Cargo.toml has now, the bumped one only applies to the next build
+    let build_name = lines[lib_pos+name_pos].split('"').nth(1).unwrap().to_string();
+    let build_version = build_number(&build_name).unwrap_or(0);
+
+    lines[lib_pos+name_pos] = format!("name = \"computer_{}\"", latest_version.max(build_version)+1);
 
     let new_cargo_toml = lines.join("\n");
 
@@ -46,4 +67,24 @@ fn main() {
         std::fs::write("Cargo.toml", new_cargo_toml).unwrap();
     }
 
-}
\ No newline at end of file
+    let version = format!("{}+build.{}", env!("CARGO_PKG_VERSION"), build_version);
+    let commit = git_commit();
+
+    let manifest = serde_json::to_string_pretty(&Manifest {
+        name: &build_name,
+        version: &version,
+        git_commit: &commit,
+        build_time,
+        plugins: &PLUGINS,
+        abi_version: ABI_VERSION,
+    }).unwrap();
+    // `build.sh` moves it next to the artifact once the build succeeded
+    std::fs::create_dir_all("target").unwrap();
+    std::fs::write(Path::new("target").join("pending.manifest.json"), manifest).unwrap();
+
+    println!("cargo:rustc-env=COMPUTER_BUILD_NAME={}", build_name);
+    println!("cargo:rustc-env=COMPUTER_VERSION={}", version);
+    println!("cargo:rustc-env=COMPUTER_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=COMPUTER_BUILD_TIME={}", build_time);
+    println!("cargo:rustc-env=COMPUTER_ABI_VERSION={}", ABI_VERSION);
+}
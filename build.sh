@@ -1 +1,4 @@
-cargo build --release --out-dir=./dist -Z unstable-options
\ No newline at end of file
+set -e
+cargo build --release --out-dir=./dist -Z unstable-options
+name=$(sed -n 's/^  "name": "\(.*\)",$/\1/p' target/pending.manifest.json)
+mv target/pending.manifest.json "dist/$name.manifest.json"
@@ -0,0 +1,195 @@
+use super::*;
+
+use utils::{predictive_shoot_at, improved_fly_to, deactivate_weapons};
+use plugins::SpacecraftHealth;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Blackboard {
+    pub target: Option<GameObjectId>,
+    pub waypoint: Option<Vec2>,
+    pub flags: HashMap<String, bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BehaviourTree {
+    pub root: Node,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Node {
+    Sequence(Vec<Node>),
+    Selector(Vec<Node>),
+    Inverter(Box<Node>),
+    Succeeder(Box<Node>),
+    Condition(Condition),
+    Action(Action),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Condition {
+    HasTarget,
+    TargetWithin(f32),
+    EnemyWithin(f32),
+    AsteroidWithin(f32),
+    MyStarBaseWithin(f32),
+    /// Fraction of the built components that were destroyed, the same measure auto retreat uses
+    LossesAbove(f32),
+    Flag(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Action {
+    TargetClosestEnemy,
+    TargetClosestEnemySpacecraft,
+    TargetClosestEnemyStarBase,
+    TargetClosestAsteroid,
+    TargetClosestMyStarBase,
+    ClearTarget,
+    ShootTarget,
+    FlyToTarget,
+    FlyToWaypoint,
+    DeactivateWeapons,
+    SetFlag(String, bool),
+}
+
+impl BehaviourTree {
+    pub fn tick(
+        &self,
+        spacecraft: (&GameObjectId, &Spacecraft),
+        game_data: &mut GameData,
+        blackboard: &mut Blackboard,
+    ) -> Status {
+        self.root.tick(spacecraft, game_data, blackboard)
+    }
+}
+
+impl Node {
+    pub fn tick(
+        &self,
+        spacecraft: (&GameObjectId, &Spacecraft),
+        game_data: &mut GameData,
+        blackboard: &mut Blackboard,
+    ) -> Status {
+        match self {
+            Node::Sequence(children) => {
+                for child in children {
+                    let status = child.tick(spacecraft, game_data, blackboard);
+                    if status != Status::Success {
+                        return status;
+                    }
+                }
+                Status::Success
+            }
+            Node::Selector(children) => {
+                for child in children {
+                    let status = child.tick(spacecraft, game_data, blackboard);
+                    if status != Status::Failure {
+                        return status;
+                    }
+                }
+                Status::Failure
+            }
+            Node::Inverter(child) => match child.tick(spacecraft, game_data, blackboard) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::Succeeder(child) => {
+                child.tick(spacecraft, game_data, blackboard);
+                Status::Success
+            }
+            Node::Condition(condition) => {
+                if condition.check(spacecraft.1, game_data, blackboard) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            Node::Action(action) => action.run(spacecraft, game_data, blackboard),
+        }
+    }
+}
+
+impl Condition {
+    pub fn check(&self, spacecraft: &Spacecraft, game_data: &GameData, blackboard: &Blackboard) -> bool {
+        let position = spacecraft.body.position;
+        let within = |body: Option<GameObjectBody>, distance: f32| {
+            body.is_some_and(|body| body.position.distance(position) <= distance)
+        };
+        match self {
+            Condition::HasTarget => blackboard.target.and_then(|id| game_data.game_object_body(id)).is_some(),
+            Condition::TargetWithin(distance) => within(blackboard.target.and_then(|id| game_data.game_object_body(id)), *distance),
+            Condition::EnemyWithin(distance) => within(game_data.closest_enemy_spacecraft(&position).map(|x| x.1.body), *distance),
+            Condition::AsteroidWithin(distance) => within(game_data.closest_asteroid(&position).map(|x| x.1.body), *distance),
+            Condition::MyStarBaseWithin(distance) => within(game_data.closest_my_star_base(&position).map(|x| x.1.body), *distance),
+            Condition::LossesAbove(threshold) => SpacecraftHealth::new(spacecraft).losses(spacecraft) >= *threshold,
+            Condition::Flag(name) => blackboard.flags.get(name).copied().unwrap_or(false),
+        }
+    }
+}
+
+impl Action {
+    pub fn run(
+        &self,
+        (id, spacecraft): (&GameObjectId, &Spacecraft),
+        game_data: &mut GameData,
+        blackboard: &mut Blackboard,
+    ) -> Status {
+        let position = spacecraft.body.position;
+        let set_target = |target: Option<GameObjectId>, blackboard: &mut Blackboard| {
+            blackboard.target = target;
+            if target.is_some() {
+                Status::Success
+            } else {
+                Status::Failure
+            }
+        };
+        match self {
+            Action::TargetClosestEnemy => set_target(game_data.closest_enemy_target(&position).map(|x| x.0), blackboard),
+            Action::TargetClosestEnemySpacecraft => set_target(game_data.closest_enemy_spacecraft(&position).map(|x| x.0), blackboard),
+            Action::TargetClosestEnemyStarBase => set_target(game_data.closest_enemy_star_base(&position).map(|x| x.0), blackboard),
+            Action::TargetClosestAsteroid => set_target(game_data.closest_asteroid(&position).map(|x| x.0), blackboard),
+            Action::TargetClosestMyStarBase => set_target(game_data.closest_my_star_base(&position).map(|x| x.0), blackboard),
+            Action::ClearTarget => set_target(None, blackboard),
+            Action::ShootTarget => {
+                let Some(target) = blackboard.target.and_then(|id| game_data.game_object_body(id)) else {
+                    return Status::Failure;
+                };
+                game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target));
+                Status::Success
+            }
+            Action::FlyToTarget => {
+                let Some(target) = blackboard.target.and_then(|id| game_data.game_object_body(id)) else {
+                    return Status::Failure;
+                };
+                game_data.execute_cmds(improved_fly_to((id, spacecraft), target));
+                Status::Running
+            }
+            Action::FlyToWaypoint => {
+                let Some(waypoint) = blackboard.waypoint else {
+                    return Status::Failure;
+                };
+                let mut target = spacecraft.body.clone();
+                target.position = waypoint;
+                target.velocity = Vec2::ZERO;
+                game_data.execute_cmds(improved_fly_to((id, spacecraft), target));
+                Status::Running
+            }
+            Action::DeactivateWeapons => {
+                game_data.execute_cmds(deactivate_weapons((id, spacecraft)));
+                Status::Success
+            }
+            Action::SetFlag(name, value) => {
+                blackboard.flags.insert(name.clone(), *value);
+                Status::Success
+            }
+        }
+    }
+}
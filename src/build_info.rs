@@ -0,0 +1,20 @@
+use super::*;
+
+/// Identity of this computer build, filled in by `build.rs`
+pub const BUILD_NAME: &str = env!("COMPUTER_BUILD_NAME");
+pub const VERSION: &str = env!("COMPUTER_VERSION");
+pub const GIT_COMMIT: &str = env!("COMPUTER_GIT_COMMIT");
+pub const BUILD_TIME: &str = env!("COMPUTER_BUILD_TIME");
+pub const ABI_VERSION: &str = env!("COMPUTER_ABI_VERSION");
+
+pub fn summary() -> String {
+    format!("{} v{} ({})", BUILD_NAME, VERSION, GIT_COMMIT)
+}
+
+pub fn ui(ui: &mut egui::Ui) {
+    ui.collapsing(format!("Build: {}", summary()), |ui| {
+        ui.label(format!("Built at: {} (unix seconds)", BUILD_TIME));
+        ui.label(format!("ABI version: {}", ABI_VERSION));
+        ui.label(format!("Plugins: {}", registered_plugins::PLUGINS.join(", ")));
+    });
+}
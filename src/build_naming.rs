@@ -0,0 +1,29 @@
+/// Build number of a file in `dist/` named like `libcomputer_3.so` or `computer_3.manifest.json`
+pub fn build_number(file_name: &str) -> Option<u32> {
+    let stem = file_name.split('.').next()?;
+    let (prefix, number) = stem.rsplit_once('_')?;
+    if !prefix.ends_with("computer") {
+        return None;
+    }
+    number.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_artifacts_and_manifests() {
+        assert_eq!(build_number("libcomputer_3.so"), Some(3));
+        assert_eq!(build_number("computer_12.dll"), Some(12));
+        assert_eq!(build_number("computer_7.manifest.json"), Some(7));
+    }
+
+    #[test]
+    fn ignores_unrelated_files() {
+        assert_eq!(build_number("README.md"), None);
+        assert_eq!(build_number("other_3.so"), None);
+        assert_eq!(build_number("computer_latest.so"), None);
+        assert_eq!(build_number(".gitkeep"), None);
+    }
+}
@@ -0,0 +1,158 @@
+use super::*;
+
+use structure_loader::StructureLoader;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OrderStatus {
+    Pending,
+    WaitingForMaterials,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildOrder {
+    pub id: u64,
+    pub structure_name: String,
+    pub structure: SpacecraftStructure,
+    pub priority: i32,
+    pub quantity: usize,
+    pub star_base: Option<GameObjectId>,
+    pub status: OrderStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringOrder {
+    pub structure_name: String,
+    pub tag: String,
+    pub minimum_alive: usize,
+    pub priority: i32,
+    pub enabled: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl RecurringOrder {
+    pub fn shortfall(&self, structure: &SpacecraftStructure, existing: usize) -> Result<usize, String> {
+        if !structure.tags.contains(&self.tag) {
+            return Err(format!("{} does not carry the tag {:?}", self.structure_name, self.tag));
+        }
+        Ok(self.minimum_alive.saturating_sub(existing))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildQueue {
+    next_id: u64,
+    pub orders: Vec<BuildOrder>,
+    pub recurring: Vec<RecurringOrder>,
+}
+
+impl BuildQueue {
+    pub fn push(&mut self, structure_name: String, structure: SpacecraftStructure, priority: i32, quantity: usize, star_base: Option<GameObjectId>) {
+        self.next_id += 1;
+        self.orders.push(BuildOrder {
+            id: self.next_id,
+            structure_name,
+            structure,
+            priority,
+            quantity,
+            star_base,
+            status: OrderStatus::Pending,
+        });
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.orders.retain(|order| order.id != id);
+    }
+
+    pub fn sorted_ids(&self) -> Vec<u64> {
+        let mut orders = self.orders.iter().collect::<Vec<_>>();
+        orders.sort_by_key(|order| (-order.priority, order.id));
+        orders.into_iter().map(|order| order.id).collect()
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut BuildOrder> {
+        self.orders.iter_mut().find(|order| order.id == id)
+    }
+
+    /// Spacecraft with the tag that are alive, in a hangar or still queued
+    pub fn count_tagged(&self, tag: &String, game_data: &GameData) -> usize {
+        let alive = game_data.my_spacecrafts().values().filter(|spacecraft| spacecraft.tags.contains(tag)).count();
+        let building = game_data.my_star_bases().values()
+            .flat_map(|star_base| star_base.hangars.iter().flat_map(|hangar| hangar.building_queue.iter()))
+            .filter(|structure| structure.tags.contains(tag))
+            .count();
+        alive + building + self.queued_tagged(tag)
+    }
+
+    pub fn queued_tagged(&self, tag: &String) -> usize {
+        self.orders.iter()
+            .filter(|order| order.structure.tags.contains(tag))
+            .map(|order| order.quantity)
+            .sum()
+    }
+
+    pub fn update_recurring(&mut self, game_data: &GameData, structures: &StructureLoader) {
+        for index in 0..self.recurring.len() {
+            let recurring = self.recurring[index].clone();
+            if !recurring.enabled {
+                continue;
+            }
+            let Some(structure) = structures.structure(&recurring.structure_name) else {
+                self.recurring[index].error = Some(format!("{} is not loaded", recurring.structure_name));
+                continue;
+            };
+            let shortfall = recurring.shortfall(structure, self.count_tagged(&recurring.tag, game_data));
+            self.recurring[index].error = shortfall.as_ref().err().cloned();
+            if let Ok(quantity) = shortfall && quantity > 0 {
+                self.push(recurring.structure_name, structure.clone(), recurring.priority, quantity, None);
+            }
+        }
+    }
+
+    pub fn material_needs(&self) -> HashMap<Material, f32> {
+        let mut needs = HashMap::new();
+        for order in &self.orders {
+            for (material, amount) in order.structure.materials() {
+                *needs.entry(material).or_default() += amount * order.quantity as f32;
+            }
+        }
+        needs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recurring(tag: &str, minimum_alive: usize) -> RecurringOrder {
+        RecurringOrder {
+            structure_name: "miner".into(),
+            tag: tag.into(),
+            minimum_alive,
+            priority: 0,
+            enabled: true,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn shortfall_requires_the_structure_tag() {
+        let structure = spacecraft_structures::asteroid_miner();
+        assert!(recurring("gunship", 3).shortfall(&structure, 0).is_err());
+        assert_eq!(recurring("asteroid_miner", 3).shortfall(&structure, 1), Ok(2));
+        assert_eq!(recurring("asteroid_miner", 3).shortfall(&structure, 5), Ok(0));
+    }
+
+    #[test]
+    fn queued_orders_count_towards_the_minimum() {
+        let structure = spacecraft_structures::asteroid_miner();
+        let order = recurring("asteroid_miner", 3);
+        let mut queue = BuildQueue::default();
+
+        let quantity = order.shortfall(&structure, queue.queued_tagged(&order.tag)).unwrap();
+        queue.push(order.structure_name.clone(), structure.clone(), order.priority, quantity, None);
+        assert_eq!(queue.queued_tagged(&order.tag), 3);
+        assert_eq!(order.shortfall(&structure, queue.queued_tagged(&order.tag)), Ok(0));
+    }
+}
@@ -0,0 +1,70 @@
+use std::{sync::Mutex, time::Instant};
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, strum::EnumIter, strum::Display)]
+pub enum Layer {
+    Targets,
+    Intercepts,
+    Velocities,
+    Waypoints,
+}
+
+impl Layer {
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            Layer::Targets => egui::Color32::RED,
+            Layer::Intercepts => egui::Color32::YELLOW,
+            Layer::Velocities => egui::Color32::LIGHT_BLUE,
+            Layer::Waypoints => egui::Color32::GREEN,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Line(Vec2, Vec2),
+    Velocity(Vec2, Vec2),
+    Circle(Vec2, f32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Drawing {
+    pub shapes: Vec<Shape>,
+    pub time: Instant,
+}
+
+static DRAWINGS: Mutex<BTreeMap<(String, Layer), Drawing>> = Mutex::new(BTreeMap::new());
+
+static VIEWED: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Camera of the host set through `set_camera`, as center and pixels per world unit
+pub static HOST_CAMERA: Mutex<Option<(Vec2, f32)>> = Mutex::new(None);
+
+pub fn keep_alive() {
+    *VIEWED.lock().unwrap() = Some(Instant::now());
+}
+
+pub fn enabled() -> bool {
+    !simulation::running() && VIEWED.lock().unwrap().is_some_and(|viewed| viewed.elapsed().as_secs_f32() < 1.)
+}
+
+pub fn draw(layer: Layer, spacecraft_id: &GameObjectId, shapes: Vec<Shape>) {
+    if !enabled() {
+        return;
+    }
+    DRAWINGS.lock().unwrap().insert((spacecraft_id.to_string(), layer), Drawing { shapes, time: Instant::now() });
+}
+
+pub fn drawings(max_age: f32) -> Vec<((String, Layer), Drawing)> {
+    let mut drawings = DRAWINGS.lock().unwrap();
+    drawings.retain(|_, drawing| drawing.time.elapsed().as_secs_f32() < max_age);
+    drawings.iter().map(|(key, drawing)| (key.clone(), drawing.clone())).collect()
+}
+
+/// Lets the host share its camera so the overlay lines up with the rendered world
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub extern fn set_camera(center_x: f32, center_y: f32, pixels_per_unit: f32) {
+    *HOST_CAMERA.lock().unwrap() = Some((Vec2::new(center_x, center_y), pixels_per_unit));
+}
@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+
+use super::*;
+
+use plugins::SpacecraftState;
+use structure_loader::StructureLoader;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DeploymentMatch {
+    Tag(String),
+    Structure(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentRule {
+    pub enabled: bool,
+    pub matches: DeploymentMatch,
+    pub batch_size: usize,
+    /// Hold while an enemy spacecraft is within this distance of the star base, zero disables the check
+    pub safe_radius: f32,
+    pub initial_state: Option<SpacecraftState>,
+}
+
+impl Default for DeploymentRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            matches: DeploymentMatch::Tag(String::new()),
+            batch_size: 1,
+            safe_radius: 0.,
+            initial_state: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentRecord {
+    pub spacecraft: GameObjectId,
+    pub star_base: GameObjectId,
+    pub hangar: usize,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeploymentPolicy {
+    pub rules: Vec<DeploymentRule>,
+    pub records: VecDeque<DeploymentRecord>,
+}
+
+fn same_structure(a: &SpacecraftStructure, b: &SpacecraftStructure) -> bool {
+    let same = |x: &ComponentPlaceholder, y: &ComponentPlaceholder| {
+        x.component_type == y.component_type && x.position == y.position && x.orientation.to_radians() == y.orientation.to_radians()
+    };
+    a.component_placeholders.len() == b.component_placeholders.len()
+        && a.component_placeholders.iter().all(|x| b.component_placeholders.iter().any(|y| same(x, y)))
+}
+
+impl DeploymentPolicy {
+    fn rule_for(&self, structure: &SpacecraftStructure, structures: &StructureLoader) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.enabled && match &rule.matches {
+            DeploymentMatch::Tag(tag) => structure.tags.contains(tag),
+            DeploymentMatch::Structure(name) => structures.structure(name).is_some_and(|x| same_structure(x, structure)),
+        })
+    }
+
+    /// Deploys finished hangars that pass their rule, hangars without a rule deploy only if `auto_deploy` is set
+    pub fn deploy(&mut self, game_data: &mut GameData, structures: &StructureLoader, auto_deploy: bool) {
+        let mut finished = vec![];
+        for (star_base_id, star_base) in game_data.my_star_bases() {
+            for (hangar_index, hangar) in star_base.hangars.iter().enumerate() {
+                let Some(structure) = hangar.building_queue.first() else {
+                    continue;
+                };
+                if hangar.build_finished() {
+                    finished.push((star_base_id, star_base.body.position, hangar_index, structure.clone(), self.rule_for(structure, structures)));
+                }
+            }
+        }
+
+        for (star_base_id, position, hangar_index, _, rule_index) in &finished {
+            let rule = rule_index.map(|index| &self.rules[index]);
+            let deploy = match rule {
+                None => auto_deploy,
+                Some(rule) => {
+                    let batch = finished.iter().filter(|x| x.4 == *rule_index).count();
+                    let threatened = rule.safe_radius > 0. && game_data.closest_enemy_spacecraft(position)
+                        .is_some_and(|(_, enemy)| enemy.body.position.distance(*position) < rule.safe_radius);
+                    batch >= rule.batch_size && !threatened
+                }
+            };
+            if !deploy {
+                continue;
+            }
+            let initial_state = rule.and_then(|rule| rule.initial_state.clone());
+
+            let before = game_data.my_spacecrafts();
+            if game_data.try_execute_cmd(GameCmd::DeploySpacecraft(*star_base_id, *hangar_index)).is_err() {
+                continue;
+            }
+            for (spacecraft_id, spacecraft) in game_data.my_spacecrafts() {
+                if before.contains_key(&spacecraft_id) {
+                    continue;
+                }
+                if let Some(state) = &initial_state {
+                    game_data.assign_state(spacecraft_id, state.clone());
+                }
+                self.records.push_front(DeploymentRecord {
+                    spacecraft: spacecraft_id,
+                    star_base: *star_base_id,
+                    hangar: *hangar_index,
+                    tags: spacecraft.tags,
+                });
+                self.records.truncate(100);
+            }
+        }
+    }
+}
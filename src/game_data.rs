@@ -1,21 +1,65 @@
 use super::*;
 
+use plugins::SpacecraftState;
+use logging::warn;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildRequest {
+    pub tags: Vec<String>,
+    pub star_base: Option<GameObjectId>,
+    #[serde(default)]
+    pub unmatched: u32,
+}
+
+impl BuildRequest {
+    pub fn new(tags: Vec<String>, star_base: Option<GameObjectId>) -> Self {
+        Self { tags, star_base, unmatched: 0 }
+    }
+}
+
+const MAX_BUILD_REQUESTS: usize = 32;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SharedState {
+    pub build_requests: Vec<BuildRequest>,
+    pub material_needs: HashMap<Material, f32>,
+    pub projected_income: HashMap<Material, f32>,
+    pub state_assignments: HashMap<GameObjectId, SpacecraftState>,
+    pub behaviour_names: Vec<String>,
+}
+
 #[derive(Debug)]
 pub struct GameData<'a> {
     pub game: &'a mut Game, 
     pub player_id: PlayerId,
     pub network_game_cmds: &'a mut Vec<GameCmd>,
+    pub shared: &'a mut SharedState,
 }
 
 impl<'a> GameData<'a> {
-    pub fn new(game: &'a mut Game, player_id: PlayerId, network_game_cmds: &'a mut Vec<GameCmd>) -> Self {
+    pub fn new(game: &'a mut Game, player_id: PlayerId, network_game_cmds: &'a mut Vec<GameCmd>, shared: &'a mut SharedState) -> Self {
         Self {
             game,
             player_id,
             network_game_cmds,
+            shared,
+        }
+    }
+
+    pub fn request_build(&mut self, request: BuildRequest) {
+        self.shared.build_requests.push(request);
+        if self.shared.build_requests.len() > MAX_BUILD_REQUESTS {
+            let dropped = self.shared.build_requests.remove(0);
+            warn!("Dropping build request for {:?}, too many are pending", dropped.tags);
         }
     }
 
+    pub fn assign_state(&mut self, spacecraft_id: GameObjectId, state: SpacecraftState) {
+        let game_objects = &self.game.game_objects;
+        self.shared.state_assignments.retain(|id, _| game_objects.contains_key(id));
+        self.shared.state_assignments.insert(spacecraft_id, state);
+    }
+
     pub fn player(&self) -> &Player {
         self.game.players.get(&self.player_id).unwrap()
     }
@@ -75,6 +119,18 @@ impl<'a> GameData<'a> {
             })
             .min_by_key(|(_, star_base)| position.distance(star_base.body.position) as u32)
     }
+    pub fn asteroids_by_id(&self) -> BTreeMap<GameObjectId, Asteroid> {
+        self.game_objects
+            .iter()
+            .filter_map(|(id, game_object)| {
+                if let GameObject::Asteroid(asteroid) = game_object {
+                    Some((*id, asteroid.clone()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
     pub fn closest_asteroid(&self, position: &Vec2) -> Option<(GameObjectId, Asteroid)> {
         self.game_objects
             .iter()
@@ -157,12 +213,25 @@ impl<'a> GameData<'a> {
             .min_by_key(|(_, body)| position.distance(body.position) as u32)
     }
 
+    pub fn game_object_body(&self, id: GameObjectId) -> Option<GameObjectBody> {
+        match self.game_objects.get(&id)? {
+            GameObject::Spacecraft(spacecraft) => Some(spacecraft.body.clone()),
+            GameObject::StarBase(star_base) => Some(star_base.body.clone()),
+            GameObject::Asteroid(asteroid) => Some(asteroid.body.clone()),
+            _ => None,
+        }
+    }
+
     pub fn execute_cmd(&mut self, cmd: GameCmd) {
-        if let Err(err) = self.game.execute_cmd(User::Player(self.player_id), cmd.clone()) {
-            println!("Error executing cmd: {:?}", err);
-            return;
+        if let Err(err) = self.try_execute_cmd(cmd) {
+            warn!("Error executing cmd: {}", err);
         }
+    }
+
+    pub fn try_execute_cmd(&mut self, cmd: GameCmd) -> Result<(), String> {
+        self.game.execute_cmd(User::Player(self.player_id), cmd.clone()).map_err(|err| format!("{:?}", err))?;
         self.network_game_cmds.push(cmd);
+        Ok(())
     }
 
     pub fn execute_cmds(&mut self, cmds: Vec<GameCmd>) {
@@ -0,0 +1,176 @@
+use super::*;
+
+use plugins::BuildSpacecrafts;
+use logging::{info, warn, error};
+
+/// Version of the handoff blob layout, bump when `Handoff` itself changes
+pub const HANDOFF_FORMAT_VERSION: u32 = 1;
+
+pub static mut PENDING_HANDOFF: Option<Handoff> = None;
+
+#[derive(Serialize, Deserialize)]
+pub struct PluginHandoff {
+    pub key: String,
+    pub enabled: bool,
+    pub state_version: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Handoff {
+    pub format_version: u32,
+    pub build_name: String,
+    pub plugins: Vec<PluginHandoff>,
+    pub shared: Vec<u8>,
+}
+
+impl Handoff {
+    pub fn new(plugin_manager: &PluginManager) -> anyhow::Result<Self> {
+        let plugins = plugin_manager.plugins.iter().filter_map(|(enabled, plugin)| {
+            let (state_version, data) = plugin.save_state()?;
+            Some(PluginHandoff {
+                key: plugin.handoff_key(),
+                enabled: *enabled,
+                state_version,
+                data,
+            })
+        }).collect();
+        Ok(Self {
+            format_version: HANDOFF_FORMAT_VERSION,
+            build_name: build_info::BUILD_NAME.into(),
+            plugins,
+            shared: serialize_bytes(&plugin_manager.shared)?,
+        })
+    }
+
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        // The format version leads the blob so incompatible layouts are rejected before decoding the rest
+        let format_version = u32::from_le_bytes(data.get(..4).ok_or(anyhow::anyhow!("handoff too short"))?.try_into()?);
+        if format_version != HANDOFF_FORMAT_VERSION {
+            anyhow::bail!("unsupported handoff format {}", format_version);
+        }
+        Ok(deserialize_bytes(&data[4..])?)
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut result = self.format_version.to_le_bytes().to_vec();
+        result.extend(serialize_bytes(self)?);
+        Ok(result)
+    }
+
+    pub fn restore(self, plugin_manager: &mut PluginManager) {
+        info!("Restoring handoff from {}", self.build_name);
+        match deserialize_bytes(&self.shared) {
+            Ok(shared) => plugin_manager.shared = shared,
+            Err(err) => error!("Error restoring shared state: {:?}", err),
+        }
+
+        for plugin_handoff in self.plugins {
+            // Production plugins get new instance numbers in this build, so their keys cannot be matched
+            let index = if plugin_handoff.key.starts_with(BuildSpacecrafts::HANDOFF_PREFIX) {
+                plugin_manager.plugins.push((false, Box::new(BuildSpacecrafts::new(spacecraft_structures_path()))));
+                plugin_manager.plugins.len() - 1
+            } else if let Some(index) = plugin_manager.plugins.iter().position(|(_, plugin)| plugin.handoff_key() == plugin_handoff.key) {
+                index
+            } else {
+                warn!("No plugin to restore {} into", plugin_handoff.key);
+                continue;
+            };
+            let (enabled, plugin) = &mut plugin_manager.plugins[index];
+            match plugin.load_state(plugin_handoff.state_version, &plugin_handoff.data) {
+                Ok(()) => *enabled = plugin_handoff.enabled,
+                Err(err) => error!("Error restoring {}: {}", plugin_handoff.key, err),
+            }
+        }
+    }
+}
+
+/// Serializes the plugin manager of this build into `out`, called by the host before unloading it
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub extern fn export_handoff(out: *mut Vec<u8>) -> bool {
+    let out = unsafe { &mut *out };
+    let Some(plugin_manager) = (unsafe { PLUGIN_MANAGER.as_mut() }) else {
+        return false;
+    };
+    let handoff = Handoff::new(plugin_manager).and_then(|handoff| handoff.to_bytes());
+    for (_, plugin) in &mut plugin_manager.plugins {
+        plugin.unload();
+    }
+    match handoff {
+        Ok(data) => {
+            *out = data;
+            true
+        }
+        Err(err) => {
+            error!("Error exporting handoff: {}", err);
+            false
+        }
+    }
+}
+
+/// Receives the blob of the previous build, called by the host after loading this one and before the first `execute`
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub extern fn init(handoff: *const Vec<u8>) -> bool {
+    if handoff.is_null() {
+        return true;
+    }
+    let data = unsafe { &*handoff };
+    match Handoff::from_bytes(data) {
+        Ok(handoff) => {
+            unsafe { PENDING_HANDOFF = Some(handoff) };
+            true
+        }
+        Err(err) => {
+            error!("Error importing handoff: {}", err);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handoff() -> Handoff {
+        Handoff {
+            format_version: HANDOFF_FORMAT_VERSION,
+            build_name: "computer_1".into(),
+            plugins: vec![PluginHandoff {
+                key: "Telemetry".into(),
+                enabled: true,
+                state_version: 2,
+                data: vec![1, 2, 3],
+            }],
+            shared: vec![4, 5],
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let restored = Handoff::from_bytes(&handoff().to_bytes().unwrap()).unwrap();
+        assert_eq!(restored.format_version, HANDOFF_FORMAT_VERSION);
+        assert_eq!(restored.build_name, "computer_1");
+        assert_eq!(restored.shared, vec![4, 5]);
+        let [plugin] = &restored.plugins[..] else {
+            panic!("expected one plugin, got {}", restored.plugins.len());
+        };
+        assert_eq!((plugin.key.as_str(), plugin.enabled, plugin.state_version), ("Telemetry", true, 2));
+        assert_eq!(plugin.data, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn other_format_versions_are_rejected() {
+        let mut data = handoff().to_bytes().unwrap();
+        data[..4].copy_from_slice(&(HANDOFF_FORMAT_VERSION + 1).to_le_bytes());
+        assert!(Handoff::from_bytes(&data).is_err());
+    }
+
+    #[test]
+    fn truncated_blobs_are_rejected() {
+        let data = handoff().to_bytes().unwrap();
+        assert!(Handoff::from_bytes(&data[..3]).is_err());
+        assert!(Handoff::from_bytes(&data[..data.len() - 1]).is_err());
+    }
+}
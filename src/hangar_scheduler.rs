@@ -0,0 +1,134 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use super::*;
+
+use build_queue::BuildOrder;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedBuild {
+    pub order_id: u64,
+    pub star_base: GameObjectId,
+    pub hangar: usize,
+    pub materials_ready: f32,
+    pub completion: f32,
+}
+
+const LOG_CAPACITY: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HangarScheduler {
+    /// Seconds of extra build time worth one unit of distance to where the spacecraft is needed
+    pub distance_weight: f32,
+    plan: Vec<PlannedBuild>,
+    known_star_bases: BTreeSet<GameObjectId>,
+    log: VecDeque<String>,
+}
+
+impl HangarScheduler {
+    pub fn new() -> Self {
+        Self {
+            distance_weight: 0.01,
+            plan: vec![],
+            known_star_bases: BTreeSet::new(),
+            log: VecDeque::new(),
+        }
+    }
+
+    pub fn first_planned(&self, order_id: u64) -> Option<&PlannedBuild> {
+        self.plan.iter().find(|planned| planned.order_id == order_id)
+    }
+
+    fn needed_at(order: &BuildOrder, game_data: &GameData, star_bases: &BTreeMap<GameObjectId, StarBase>) -> Option<Vec2> {
+        if let Some(star_base) = order.star_base.and_then(|id| star_bases.get(&id)) {
+            return Some(star_base.body.position);
+        }
+        let positions = game_data.my_spacecrafts().into_values()
+            .filter(|spacecraft| order.structure.tags.iter().any(|tag| spacecraft.tags.contains(tag)))
+            .map(|spacecraft| spacecraft.body.position)
+            .collect::<Vec<_>>();
+        if positions.is_empty() {
+            return None;
+        }
+        Some(positions.iter().sum::<Vec2>() / positions.len() as f32)
+    }
+
+    pub fn update_star_bases(&mut self, orders: &mut [BuildOrder], game_data: &GameData) {
+        let current = game_data.my_star_bases().into_keys().collect::<BTreeSet<_>>();
+        for star_base_id in self.known_star_bases.difference(&current) {
+            self.log.push_front(format!("Star base {} lost, re-planning", star_base_id));
+            self.log.truncate(LOG_CAPACITY);
+            for order in orders.iter_mut().filter(|order| order.star_base == Some(*star_base_id)) {
+                order.star_base = None;
+            }
+        }
+        self.known_star_bases = current;
+    }
+
+    fn materials_ready(needed: &HashMap<Material, f32>, game_data: &GameData) -> f32 {
+        let stock = &game_data.player().materials;
+        needed.iter().map(|(material, amount)| {
+            let missing = amount - stock.get(material).copied().unwrap_or(0.);
+            if missing <= 0. {
+                return 0.;
+            }
+            match game_data.shared.projected_income.get(material) {
+                Some(income) if *income > 0. => missing / income,
+                _ => f32::INFINITY,
+            }
+        }).fold(0., f32::max)
+    }
+
+    /// Places every unit, highest priority first, in the hangar where it is done soonest
+    pub fn replan(&mut self, orders: &[&BuildOrder], game_data: &GameData) {
+        let star_bases = game_data.my_star_bases();
+        let mut hangars = star_bases.iter().flat_map(|(id, star_base)| {
+            star_base.hangars.iter().enumerate().map(move |(index, hangar)| {
+                let total_build_time = hangar.building_queue.iter().map(|structure| structure.build_time()).sum::<f32>();
+                (*id, index, star_base.body.position, total_build_time - hangar.progress.min(total_build_time))
+            })
+        }).collect::<Vec<_>>();
+
+        self.plan.clear();
+        let mut needed = HashMap::new();
+        for order in orders {
+            let needed_at = Self::needed_at(order, game_data, &star_bases);
+            let build_time = order.structure.build_time();
+            for _ in 0..order.quantity {
+                for (material, amount) in order.structure.materials() {
+                    *needed.entry(material).or_insert(0.) += amount;
+                }
+                let materials_ready = Self::materials_ready(&needed, game_data);
+                let best = hangars.iter_mut().min_by(|a, b| {
+                    let cost = |(_, _, position, free_at): &(GameObjectId, usize, Vec2, f32)| {
+                        free_at.max(materials_ready) + build_time + needed_at.map(|x| x.distance(*position) * self.distance_weight).unwrap_or(0.)
+                    };
+                    cost(a).partial_cmp(&cost(b)).unwrap_or(std::cmp::Ordering::Equal)
+                });
+                let Some((star_base, hangar, _, free_at)) = best else {
+                    return;
+                };
+                *free_at = free_at.max(materials_ready) + build_time;
+                self.plan.push(PlannedBuild {
+                    order_id: order.id,
+                    star_base: *star_base,
+                    hangar: *hangar,
+                    materials_ready,
+                    completion: *free_at,
+                });
+            }
+        }
+    }
+
+    pub fn update_ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.distance_weight, 0.0..=0.1).text("seconds per distance"));
+        for planned in &self.plan {
+            let materials = if planned.materials_ready.is_finite() { format!("{:.0}s", planned.materials_ready) } else { "never at the current income".into() };
+            ui.label(format!("Order {} at star base {} hangar {}, materials in {}, done in {:.0}s", planned.order_id, planned.star_base, planned.hangar, materials, planned.completion));
+        }
+        ui.collapsing("Log", |ui| {
+            for entry in &self.log {
+                ui.label(entry);
+            }
+        });
+    }
+}
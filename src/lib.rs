@@ -4,29 +4,130 @@ use stellar_bit_core::prelude::*;
 use serde::{Serialize, Deserialize};
 
 mod game_data;
-use game_data::GameData;
+use game_data::{GameData, BuildRequest, SharedState};
 
 mod plugins;
 use plugins::{Plugin,SpacecraftControl};
 
 mod utils;
 
+mod logging;
+
+mod debug_draw;
+
+mod behaviour_tree;
+
+mod mining_planner;
+
+mod watched_dir;
+
+mod structure_loader;
+
+mod structure_formats;
+
+mod structure_analysis;
+
+mod structure_editor;
+
+mod structure_generator;
+
+mod simulation;
+
+mod build_queue;
+
+mod hangar_scheduler;
+
+mod deployment;
+
+mod triggers;
+
+mod build_info;
+
+mod registered_plugins;
+
+#[cfg(test)]
+mod build_naming;
+
+mod handoff;
+
+mod profiler;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod plugin_libraries;
+
 mod spacecraft_structures;
 
 pub static mut STARTUP: bool = true;
 pub static mut PLUGIN_MANAGER: Option<PluginManager> = None;
 
-#[derive(Default)]
+pub fn spacecraft_structures_path() -> std::path::PathBuf {
+    concat!(env!("CARGO_MANIFEST_DIR"), "/spacecraft-structures").into()
+}
+
+pub fn behaviour_trees_path() -> std::path::PathBuf {
+    concat!(env!("CARGO_MANIFEST_DIR"), "/behaviour-trees").into()
+}
+
+pub fn scripts_path() -> std::path::PathBuf {
+    concat!(env!("CARGO_MANIFEST_DIR"), "/scripts").into()
+}
+
+pub fn wasm_plugins_path() -> std::path::PathBuf {
+    concat!(env!("CARGO_MANIFEST_DIR"), "/wasm-plugins").into()
+}
+
+pub fn telemetry_path() -> std::path::PathBuf {
+    concat!(env!("CARGO_MANIFEST_DIR"), "/telemetry").into()
+}
+
+pub fn plugin_libraries_path() -> std::path::PathBuf {
+    concat!(env!("CARGO_MANIFEST_DIR"), "/plugin-libraries").into()
+}
+
 pub struct PluginManager {
-    plugins: Vec<(bool, Box<dyn Plugin>)>
+    plugins: Vec<(bool, Box<dyn Plugin>)>,
+    shared: SharedState,
+    profiler: profiler::Profiler,
+    log_view: logging::LogView,
+    log_open: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    libraries: plugin_libraries::PluginLibraries,
+}
+
+impl Default for PluginManager {
+    fn default() -> Self {
+        Self {
+            plugins: vec![],
+            shared: Default::default(),
+            profiler: Default::default(),
+            log_view: Default::default(),
+            log_open: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            libraries: plugin_libraries::PluginLibraries::new(plugin_libraries_path()),
+        }
+    }
+}
+
+fn create_plugin(name: &str) -> Box<dyn Plugin> {
+    use plugins::{SpacecraftConstruction, StructureOptimizer, ScriptRunner, WasmSandbox, Telemetry, DebugOverlay, Minimap};
+
+    match name {
+        "SpacecraftControl" => Box::new(SpacecraftControl::new(behaviour_trees_path())),
+        "SpacecraftConstruction" => Box::new(SpacecraftConstruction::new(spacecraft_structures_path())),
+        "StructureOptimizer" => Box::new(StructureOptimizer::new(spacecraft_structures_path())),
+        "ScriptRunner" => Box::new(ScriptRunner::new(scripts_path())),
+        "WasmSandbox" => Box::new(WasmSandbox::new(wasm_plugins_path())),
+        "Telemetry" => Box::new(Telemetry::new(telemetry_path())),
+        "DebugOverlay" => Box::new(DebugOverlay::new()),
+        "Minimap" => Box::new(Minimap::new()),
+        _ => unreachable!("{} is not a registered plugin", name),
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 #[no_mangle]
 pub extern fn execute(pointers: *const (*mut Game, *const User, *const egui::Context, *mut Vec<GameCmd>)) {
-    use std::path::Path;
-
-    use plugins::SpacecraftConstruction;
+    use plugins::BuildSpacecrafts;
 
     let (game_ptr, user_ptr, egui_ctx_ptr, network_game_cmds_ptr) = unsafe { *pointers };
 
@@ -40,13 +141,12 @@ pub extern fn execute(pointers: *const (*mut Game, *const User, *const egui::Con
     
     let startup = unsafe { &mut STARTUP };
     if *startup {
-        plugin_manager.plugins = vec![];
-        plugin_manager.plugins.push(
-            (false, Box::new(SpacecraftControl::new()))
-        );
-        plugin_manager.plugins.push(
-            (false, Box::new(SpacecraftConstruction::new(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/spacecraft-structures")).into())))
-        );
+        plugin_manager.plugins = registered_plugins::PLUGINS.iter()
+            .map(|name| (false, create_plugin(name)))
+            .collect();
+        if let Some(handoff) = unsafe { handoff::PENDING_HANDOFF.take() } {
+            handoff.restore(&mut plugin_manager);
+        }
         *startup = false;
     }
 
@@ -54,24 +154,68 @@ pub extern fn execute(pointers: *const (*mut Game, *const User, *const egui::Con
         return;
     };
 
-    let mut game_data = GameData::new(game, *player_id, network_game_cmds);
+    plugin_manager.libraries.refresh();
+
+    let mut game_data = GameData::new(game, *player_id, network_game_cmds, &mut plugin_manager.shared);
 
     egui::SidePanel::new(egui::panel::Side::Right, "Plugins").show(egui_ctx, |ui| {
-        for (enabled, plugin) in plugin_manager.plugins.iter_mut() {
-            ui.checkbox(enabled, plugin.name());
+        build_info::ui(ui);
+        ui.separator();
+        let mut removed = None;
+        for (index, (enabled, plugin)) in plugin_manager.plugins.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.checkbox(enabled, plugin.name());
+                plugin_manager.profiler.summary_ui(ui, &plugin.handoff_key());
+                // Only productions are added at runtime, the other plugins are part of the build
+                if plugin.handoff_key().starts_with(BuildSpacecrafts::HANDOFF_PREFIX) && ui.small_button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            plugin_manager.plugins.remove(index);
         }
+        if ui.button("Add production").clicked() {
+            plugin_manager.plugins.push(
+                (true, Box::new(BuildSpacecrafts::new(spacecraft_structures_path())))
+            );
+        }
+        ui.separator();
+        ui.label("Libraries");
+        plugin_manager.libraries.ui(ui);
+        ui.separator();
+        ui.collapsing("Profiling", |ui| {
+            plugin_manager.profiler.ui(ui);
+        });
+        ui.checkbox(&mut plugin_manager.log_open, "Log");
     });
 
-    for (enabled, plugin) in plugin_manager.plugins.iter_mut() {
-        if !*enabled {
+    egui::Window::new("Log").open(&mut plugin_manager.log_open).show(egui_ctx, |ui| {
+        plugin_manager.log_view.ui(ui);
+    });
+
+    let builtin_plugins = plugin_manager.plugins.iter_mut().map(|(enabled, plugin)| (enabled, plugin.as_mut()));
+    let mut plugins = builtin_plugins.chain(plugin_manager.libraries.plugins_mut()).collect::<Vec<_>>();
+    let keys = plugins.iter().map(|(_, plugin)| plugin.handoff_key()).collect::<Vec<_>>();
+    for index in plugin_manager.profiler.start_frame(&keys) {
+        let (enabled, plugin) = &mut plugins[index];
+        if !**enabled {
             continue;
         }
-        egui::Window::new(format!("Plugin: {}", plugin.name())).show(egui_ctx, |ui| {
-            plugin.update_ui(ui);
+        let key = &keys[index];
+        logging::set_plugin(Some(key.clone()));
+        // The name of a production follows its tag, the key keeps the window in place while it is edited
+        plugin_manager.profiler.measure_ui(key, || {
+            egui::Window::new(format!("Plugin: {}", plugin.name())).id(egui::Id::new(key)).show(egui_ctx, |ui| {
+                plugin.update_ui(ui);
+            });
+            plugin.paint(egui_ctx);
         });
-        if plugin.update_interval().check() {
-            plugin.update(&mut game_data);
+        // Leaves the interval due so the plugin runs first thing next frame
+        if !plugin_manager.profiler.defer(index) && plugin.update_interval().check() && !plugin_manager.profiler.skip(key) {
+            plugin_manager.profiler.measure_update(key, || plugin.update(&mut game_data));
         }
+        logging::set_plugin(None);
     }
 
     unsafe { PLUGIN_MANAGER = Some(plugin_manager) };
@@ -92,13 +236,10 @@ pub extern fn execute(pointers: *const (*mut Game, *const User, *const egui::Con
 
 
 
-#[cfg(target_arch = "wasm32")]
-pub static mut PLUGIN_MANAGER: Option<PluginManager> = None;
-
 pub static mut UPDATE_TOGGLE: bool = false;
 
 #[cfg(target_arch = "wasm32")]
-#[wasm_bindgen]
+#[wasm_bindgen::prelude::wasm_bindgen]
 pub fn execute(game_bin: Vec<u8>, user_bin: Vec<u8>) -> Vec<u8> {
     let mut game: Game = deserialize_bytes(&game_bin).unwrap();
     let user: User = deserialize_bytes(&user_bin).unwrap();
@@ -109,30 +250,27 @@ pub fn execute(game_bin: Vec<u8>, user_bin: Vec<u8>) -> Vec<u8> {
 
     let mut result = vec![];
 
-    let mut game_data = GameData::new(&mut game, player_id, &mut result);
-
     if unsafe {UPDATE_TOGGLE} {
         unsafe {UPDATE_TOGGLE = !UPDATE_TOGGLE};
         unsafe {PLUGIN_MANAGER = None};
     }
 
-    let mut plugin_manager = (unsafe { PLUGIN_MANAGER.take() }).unwrap_or({
+    let mut plugin_manager = (unsafe { PLUGIN_MANAGER.take() }).unwrap_or_else(|| {
         let mut result = PluginManager::default();
-        result.plugins.push(
-            (true, Plugin::SpacecraftControl(SpacecraftControl::new("asteroid_miner".into(), plugins::SpacecraftState::Idle)))
-        );
-        result.plugins.push(
-            (true, Plugin::BuildSpacecrafts(BuildSpacecrafts::new(spacecraft_structures::asteroid_miner(), "asteroid_miner".into(), 2, None)))
-        );
+        // There is no plugin panel to switch them on
+        result.plugins = registered_plugins::PLUGINS.iter()
+            .map(|name| (matches!(*name, "SpacecraftControl" | "SpacecraftConstruction"), create_plugin(name)))
+            .collect();
         result
     });
 
+    let mut game_data = GameData::new(&mut game, player_id, &mut result, &mut plugin_manager.shared);
 
     for (enabled, plugin) in plugin_manager.plugins.iter_mut() {
         if !*enabled {
             continue;
         }
-        if plugin.interval().check() {
+        if plugin.update_interval().check() {
             plugin.update(&mut game_data);
         }
     }
@@ -140,4 +278,4 @@ pub fn execute(game_bin: Vec<u8>, user_bin: Vec<u8>) -> Vec<u8> {
     unsafe { PLUGIN_MANAGER = Some(plugin_manager) };
     
     serialize_bytes(&result).unwrap()
-}
\ No newline at end of file
+}
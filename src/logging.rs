@@ -0,0 +1,234 @@
+use std::{collections::VecDeque, fs, io::Write, path::PathBuf, sync::Mutex, time::Instant};
+
+use super::*;
+
+const CAPACITY: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, strum::EnumIter, strum::Display)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn color(&self) -> egui::Color32 {
+        match self {
+            Level::Debug => egui::Color32::GRAY,
+            Level::Info => egui::Color32::LIGHT_GRAY,
+            Level::Warn => egui::Color32::YELLOW,
+            Level::Error => egui::Color32::RED,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: f32,
+    pub level: Level,
+    pub plugin: Option<String>,
+    pub spacecraft: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>9.3} {:<5}", self.time, self.level)?;
+        if let Some(plugin) = &self.plugin {
+            write!(f, " [{}]", plugin)?;
+        }
+        if let Some(spacecraft) = &self.spacecraft {
+            write!(f, " <{}>", spacecraft)?;
+        }
+        write!(f, " {}", self.message)
+    }
+}
+
+struct Logger {
+    start: Option<Instant>,
+    entries: VecDeque<LogEntry>,
+    level: Level,
+    plugin: Option<String>,
+    file: Option<(PathBuf, fs::File)>,
+    stdout: bool,
+}
+
+static LOGGER: Mutex<Logger> = Mutex::new(Logger {
+    start: None,
+    entries: VecDeque::new(),
+    level: Level::Info,
+    plugin: None,
+    file: None,
+    stdout: false,
+});
+
+fn logger() -> std::sync::MutexGuard<'static, Logger> {
+    LOGGER.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn enabled(level: Level) -> bool {
+    !simulation::running() && level >= logger().level
+}
+
+/// Attributes the following entries to a plugin, `None` once it returns
+pub fn set_plugin(plugin: Option<String>) {
+    logger().plugin = plugin;
+}
+
+pub fn record(level: Level, spacecraft: Option<String>, message: String) {
+    let mut logger = logger();
+    if level < logger.level {
+        return;
+    }
+    let start = *logger.start.get_or_insert_with(Instant::now);
+    let entry = LogEntry {
+        time: start.elapsed().as_secs_f32(),
+        level,
+        plugin: logger.plugin.clone(),
+        spacecraft,
+        message,
+    };
+    if logger.stdout {
+        println!("{}", entry);
+    }
+    if let Some((_, file)) = &mut logger.file {
+        let _ = writeln!(file, "{}", entry);
+    }
+    logger.entries.push_back(entry);
+    if logger.entries.len() > CAPACITY {
+        logger.entries.pop_front();
+    }
+}
+
+macro_rules! log {
+    ($level:expr, spacecraft: $spacecraft:expr, $($arg:tt)+) => {
+        if $crate::logging::enabled($level) {
+            $crate::logging::record($level, Some($spacecraft.to_string()), format!($($arg)+))
+        }
+    };
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::logging::enabled($level) {
+            $crate::logging::record($level, None, format!($($arg)+))
+        }
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::logging::log!($crate::logging::Level::Debug, $($arg)+) };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::logging::log!($crate::logging::Level::Info, $($arg)+) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::logging::log!($crate::logging::Level::Warn, $($arg)+) };
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::logging::log!($crate::logging::Level::Error, $($arg)+) };
+}
+
+pub(crate) use {log, debug, info, warn, error};
+
+pub struct LogView {
+    level: Level,
+    plugin: String,
+    spacecraft: String,
+    search: String,
+    file_path: String,
+    file_error: Option<String>,
+}
+
+impl Default for LogView {
+    fn default() -> Self {
+        Self {
+            level: Level::Info,
+            plugin: String::new(),
+            spacecraft: String::new(),
+            search: String::new(),
+            file_path: concat!(env!("CARGO_MANIFEST_DIR"), "/computer.log").into(),
+            file_error: None,
+        }
+    }
+}
+
+impl LogView {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        let contains = |value: &Option<String>, filter: &str| {
+            filter.is_empty() || value.as_ref().is_some_and(|value| value.contains(filter))
+        };
+        entry.level >= self.level
+            && contains(&entry.plugin, &self.plugin)
+            && contains(&entry.spacecraft, &self.spacecraft)
+            && (self.search.is_empty() || entry.message.contains(&self.search))
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        use strum::IntoEnumIterator;
+
+        let mut logger = logger();
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Record")
+                .selected_text(logger.level.to_string())
+                .show_ui(ui, |ui| {
+                    for level in Level::iter() {
+                        ui.selectable_value(&mut logger.level, level, level.to_string());
+                    }
+                });
+            ui.checkbox(&mut logger.stdout, "stdout");
+            if ui.button("Clear").clicked() {
+                logger.entries.clear();
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut to_file = logger.file.is_some();
+            ui.checkbox(&mut to_file, "Write to");
+            ui.text_edit_singleline(&mut self.file_path);
+            if to_file && logger.file.is_none() {
+                let file = fs::OpenOptions::new().create(true).append(true).open(&self.file_path);
+                match file {
+                    Ok(file) => {
+                        logger.file = Some((self.file_path.clone().into(), file));
+                        self.file_error = None;
+                    }
+                    Err(err) => self.file_error = Some(err.to_string()),
+                }
+            } else if !to_file {
+                logger.file = None;
+            }
+        });
+        if let Some(error) = &self.file_error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Show")
+                .selected_text(self.level.to_string())
+                .show_ui(ui, |ui| {
+                    for level in Level::iter() {
+                        ui.selectable_value(&mut self.level, level, level.to_string());
+                    }
+                });
+        });
+        egui::Grid::new("log filters").show(ui, |ui| {
+            ui.label("Plugin");
+            ui.text_edit_singleline(&mut self.plugin);
+            ui.end_row();
+            ui.label("Spacecraft");
+            ui.text_edit_singleline(&mut self.spacecraft);
+            ui.end_row();
+            ui.label("Search");
+            ui.text_edit_singleline(&mut self.search);
+            ui.end_row();
+        });
+
+        egui::ScrollArea::vertical().max_height(300.).stick_to_bottom(true).show(ui, |ui| {
+            for entry in logger.entries.iter().filter(|entry| self.matches(entry)) {
+                ui.colored_label(entry.level.color(), egui::RichText::new(entry.to_string()).monospace());
+            }
+        });
+    }
+}
@@ -0,0 +1,270 @@
+use std::time::Instant;
+
+use super::*;
+
+use structure_analysis::StructureAnalysis;
+
+pub const MINING_RANGE: f32 = 200.;
+
+pub const PROJECTION_HORIZON: f32 = 60.;
+
+/// Sums the rises of the material stock between polls, spending only ever lowers it so they are what was mined
+#[derive(Debug, Default)]
+pub struct MinedTracker {
+    last_stock: Option<HashMap<Material, f32>>,
+    mined: HashMap<Material, f32>,
+}
+
+impl MinedTracker {
+    pub fn track(&mut self, stock: &HashMap<Material, f32>) {
+        if let Some(last_stock) = &self.last_stock {
+            for (material, current) in stock {
+                let previous = last_stock.get(material).copied().unwrap_or(*current);
+                *self.mined.entry(material.clone()).or_default() += (current - previous).max(0.);
+            }
+        }
+        self.last_stock = Some(stock.clone());
+    }
+
+    pub fn take(&mut self) -> HashMap<Material, f32> {
+        std::mem::take(&mut self.mined)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MiningPlanner {
+    pub targets: HashMap<Material, f32>,
+    pub follow_build_queue: bool,
+    pub max_miners_per_asteroid: usize,
+    assignments: HashMap<GameObjectId, GameObjectId>,
+    replan_interval: Interval,
+    income: HashMap<Material, f32>,
+    #[serde(default)]
+    mined: HashMap<Material, f32>,
+    #[serde(default)]
+    mined_per_miner: HashMap<Material, f32>,
+    miners_per_material: HashMap<Material, usize>,
+    #[serde(default)]
+    mining_miners: HashMap<Material, usize>,
+    #[serde(default)]
+    travel_times: HashMap<GameObjectId, (Material, f32)>,
+    #[serde(skip)]
+    accelerations: HashMap<GameObjectId, f32>,
+    last_sample: Option<HashMap<Material, f32>>,
+    #[serde(skip)]
+    last_sample_time: Option<Instant>,
+    #[serde(skip)]
+    mined_tracker: MinedTracker,
+}
+
+impl MiningPlanner {
+    pub fn new() -> Self {
+        Self {
+            targets: HashMap::new(),
+            follow_build_queue: true,
+            max_miners_per_asteroid: 3,
+            assignments: HashMap::new(),
+            replan_interval: Interval::new(time::Duration::from_secs(5)),
+            income: HashMap::new(),
+            mined: HashMap::new(),
+            mined_per_miner: HashMap::new(),
+            miners_per_material: HashMap::new(),
+            mining_miners: HashMap::new(),
+            travel_times: HashMap::new(),
+            accelerations: HashMap::new(),
+            last_sample: None,
+            last_sample_time: None,
+            mined_tracker: MinedTracker::default(),
+        }
+    }
+
+    pub fn assignment(&self, miner: &GameObjectId) -> Option<GameObjectId> {
+        self.assignments.get(miner).copied()
+    }
+
+    fn material_weights(&self, game_data: &GameData) -> HashMap<Material, f32> {
+        let stock = &game_data.player().materials;
+        let targets = if self.follow_build_queue && !game_data.shared.material_needs.is_empty() {
+            &game_data.shared.material_needs
+        } else {
+            &self.targets
+        };
+
+        let mut weights = stock.keys().map(|material| {
+            let current = stock.get(material).copied().unwrap_or(0.);
+            let deficit = targets.get(material).map(|target| (target - current).max(0.)).unwrap_or(0.);
+            (material.clone(), deficit)
+        }).collect::<HashMap<_, _>>();
+
+        // Nothing is missing, keep the stockpile balanced by favouring scarce materials
+        if weights.values().all(|deficit| *deficit <= 0.) {
+            weights = stock.iter().map(|(material, current)| (material.clone(), 1. / (1. + current.max(0.)))).collect();
+        }
+
+        let total = weights.values().sum::<f32>();
+        if total > 0. {
+            weights.values_mut().for_each(|weight| *weight /= total);
+        }
+        weights
+    }
+
+    pub fn plan(&mut self, miners: &BTreeMap<GameObjectId, Spacecraft>, game_data: &GameData) {
+        self.sample_income(game_data);
+
+        let asteroids = game_data.asteroids_by_id();
+        let assignments_valid = self.assignments.len() == miners.len()
+            && self.assignments.iter().all(|(miner, asteroid)| miners.contains_key(miner) && asteroids.contains_key(asteroid));
+        if !assignments_valid || self.replan_interval.check() {
+            self.assign(miners, &asteroids, game_data);
+        }
+        self.track_miners(miners, &asteroids);
+    }
+
+    fn assign(&mut self, miners: &BTreeMap<GameObjectId, Spacecraft>, asteroids: &BTreeMap<GameObjectId, Asteroid>, game_data: &GameData) {
+        self.assignments.clear();
+        if asteroids.is_empty() || miners.is_empty() {
+            return;
+        }
+
+        // Split the miners between materials by largest remainder
+        let weights = self.material_weights(game_data);
+        let mut quotas = weights.iter()
+            .filter(|(material, _)| asteroids.values().any(|asteroid| asteroid.material == **material))
+            .map(|(material, weight)| (material.clone(), weight * miners.len() as f32))
+            .collect::<Vec<_>>();
+        let mut remaining = miners.len().saturating_sub(quotas.iter().map(|(_, quota)| quota.floor() as usize).sum());
+        quotas.sort_by(|a, b| (b.1 - b.1.floor()).partial_cmp(&(a.1 - a.1.floor())).unwrap_or(std::cmp::Ordering::Equal));
+        let mut quotas = quotas.into_iter().map(|(material, quota)| {
+            let mut quota = quota.floor() as usize;
+            if remaining > 0 {
+                quota += 1;
+                remaining -= 1;
+            }
+            (material, quota)
+        }).collect::<HashMap<_, _>>();
+
+        let mut pairs = miners.iter().flat_map(|(miner_id, miner)| {
+            asteroids.iter().map(move |(asteroid_id, asteroid)| {
+                (miner.body.position.distance(asteroid.body.position), *miner_id, *asteroid_id)
+            })
+        }).collect::<Vec<_>>();
+        pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut crowding: HashMap<GameObjectId, usize> = HashMap::new();
+        // First pass honours the material quotas, the second one places whoever is left
+        for honour_quotas in [true, false] {
+            for (_, miner_id, asteroid_id) in &pairs {
+                if self.assignments.contains_key(miner_id) {
+                    continue;
+                }
+                let material = &asteroids[asteroid_id].material;
+                let crowded = crowding.get(asteroid_id).copied().unwrap_or(0) >= self.max_miners_per_asteroid;
+                if crowded {
+                    continue;
+                }
+                if honour_quotas {
+                    let Some(quota) = quotas.get_mut(material).filter(|quota| **quota > 0) else {
+                        continue;
+                    };
+                    *quota -= 1;
+                }
+                *crowding.entry(*asteroid_id).or_default() += 1;
+                self.assignments.insert(*miner_id, *asteroid_id);
+            }
+        }
+
+        // Every asteroid is crowded, fall back to the closest one
+        for (miner_id, miner) in miners {
+            if !self.assignments.contains_key(miner_id) && let Some((asteroid_id, _)) = game_data.closest_asteroid(&miner.body.position) {
+                self.assignments.insert(*miner_id, asteroid_id);
+            }
+        }
+    }
+
+    fn track_miners(&mut self, miners: &BTreeMap<GameObjectId, Spacecraft>, asteroids: &BTreeMap<GameObjectId, Asteroid>) {
+        self.accelerations.retain(|id, _| miners.contains_key(id));
+        self.miners_per_material.clear();
+        self.mining_miners.clear();
+        self.travel_times.clear();
+        for (miner_id, asteroid_id) in &self.assignments {
+            let (Some(miner), Some(asteroid)) = (miners.get(miner_id), asteroids.get(asteroid_id)) else {
+                continue;
+            };
+            let material = asteroid.material.clone();
+            *self.miners_per_material.entry(material.clone()).or_default() += 1;
+            let distance = (miner.body.position.distance(asteroid.body.position) - MINING_RANGE).max(0.);
+            if distance == 0. {
+                *self.mining_miners.entry(material.clone()).or_default() += 1;
+            }
+            let acceleration = *self.accelerations.entry(*miner_id)
+                .or_insert_with(|| StructureAnalysis::new(&miner.structure).max_acceleration);
+            // Accelerating for the first half of the way and braking for the second one
+            let travel_time = if acceleration > 0. { 2. * (distance / acceleration).sqrt() } else { f32::INFINITY };
+            self.travel_times.insert(*miner_id, (material, travel_time));
+        }
+    }
+
+    fn sample_income(&mut self, game_data: &GameData) {
+        let stock = game_data.player().materials.clone();
+        self.mined_tracker.track(&stock);
+
+        let now = Instant::now();
+        if let (Some(last_sample), Some(last_time)) = (&self.last_sample, self.last_sample_time) {
+            let elapsed = now.duration_since(last_time).as_secs_f32();
+            if elapsed < 1. {
+                return;
+            }
+            let mined_since_sample = self.mined_tracker.take();
+            for (material, current) in &stock {
+                let previous = last_sample.get(material).copied().unwrap_or(*current);
+                smooth(&mut self.income, material, (current - previous) / elapsed);
+                let mined = mined_since_sample.get(material).copied().unwrap_or(0.) / elapsed;
+                let mined = smooth(&mut self.mined, material, mined);
+                if let Some(miners) = self.mining_miners.get(material).filter(|miners| **miners > 0) {
+                    self.mined_per_miner.insert(material.clone(), mined / *miners as f32);
+                }
+            }
+        }
+        self.last_sample = Some(stock);
+        self.last_sample_time = Some(now);
+    }
+
+    /// Averaged over the next `horizon` seconds, assigned miners count from when they reach their asteroid
+    pub fn projected_income(&self, material: &Material, horizon: f32) -> f32 {
+        let mined_per_miner = self.mined_per_miner.get(material).copied().unwrap_or(0.);
+        self.travel_times.values()
+            .filter(|(miner_material, _)| miner_material == material)
+            .map(|(_, travel_time)| mined_per_miner * (1. - travel_time / horizon).max(0.))
+            .sum()
+    }
+
+    pub fn update_ui(&mut self, ui: &mut egui::Ui, materials: &[Material]) {
+        ui.checkbox(&mut self.follow_build_queue, "target materials needed by build queue");
+        ui.add(egui::Slider::new(&mut self.max_miners_per_asteroid, 1..=10).text("max miners per asteroid"));
+        egui::Grid::new("mining planner").show(ui, |ui| {
+            ui.label("Material");
+            ui.label("Target");
+            ui.label("Miners");
+            ui.label("Mined/s");
+            ui.label("Net/s");
+            ui.label(format!("Projected/s over {:.0} s", PROJECTION_HORIZON));
+            ui.end_row();
+            for material in materials {
+                ui.label(format!("{:?}", material));
+                let target = self.targets.entry(material.clone()).or_default();
+                ui.add(egui::DragValue::new(target).speed(10.));
+                ui.label(format!("{}", self.miners_per_material.get(material).copied().unwrap_or(0)));
+                ui.label(format!("{:.2}", self.mined.get(material).copied().unwrap_or(0.)));
+                ui.label(format!("{:.2}", self.income.get(material).copied().unwrap_or(0.)));
+                ui.label(format!("{:.2}", self.projected_income(material, PROJECTION_HORIZON)));
+                ui.end_row();
+            }
+        });
+    }
+}
+
+fn smooth(rates: &mut HashMap<Material, f32>, material: &Material, rate: f32) -> f32 {
+    let smoothed = rates.entry(material.clone()).or_insert(rate);
+    *smoothed = *smoothed * 0.8 + rate * 0.2;
+    *smoothed
+}
@@ -0,0 +1,277 @@
+use std::{ffi::c_void, fs, panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}, sync::atomic::{AtomicUsize, Ordering}, time::SystemTime};
+
+use super::*;
+
+use plugins::Plugin;
+use watched_dir::{WatchedDir, has_extension};
+
+/// Version of the byte contract below, libraries built against another version are refused
+pub const PLUGIN_ABI_VERSION: u32 = 3;
+
+/// Symbols every plugin library exports. Only plain integers and byte buffers cross the boundary, like the wasm32
+/// `execute`, so host and plugin do not need to agree on the layout of any Rust type:
+/// - `computer_plugin_abi_version() -> u32`
+/// - `computer_plugin_name(write, context) -> bool` passes the UTF-8 name to `write`
+/// - `computer_plugin_update_interval_ms() -> u64`
+/// - `computer_plugin_update(game, game_len, user, user_len, write, context) -> bool` receives `serialize_bytes` of
+///   the game and the user and passes `serialize_bytes` of a `Vec<GameCmd>` to `write`
+///
+/// A panic unwinding out of any of them aborts the process, so a library catches its own panics and returns `false`.
+/// The library runs in the host process, nothing protects the host from a library that crashes.
+pub const PLUGIN_ABI_VERSION_SYMBOL: &[u8] = b"computer_plugin_abi_version\0";
+pub const PLUGIN_NAME_SYMBOL: &[u8] = b"computer_plugin_name\0";
+pub const PLUGIN_UPDATE_INTERVAL_SYMBOL: &[u8] = b"computer_plugin_update_interval_ms\0";
+pub const PLUGIN_UPDATE_SYMBOL: &[u8] = b"computer_plugin_update\0";
+
+pub type WriteBytes = extern "C" fn(context: *mut c_void, data: *const u8, len: usize);
+
+type NameFn = extern "C" fn(write: WriteBytes, context: *mut c_void) -> bool;
+type UpdateIntervalFn = extern "C" fn() -> u64;
+type UpdateFn = extern "C" fn(game: *const u8, game_len: usize, user: *const u8, user_len: usize, write: WriteBytes, context: *mut c_void) -> bool;
+
+const MAX_NAME_LENGTH: usize = 64;
+
+static NEXT_COPY: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn write_bytes(context: *mut c_void, data: *const u8, len: usize) {
+    if data.is_null() {
+        return;
+    }
+    // Must not unwind into the library
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let buffer = unsafe { &mut *(context as *mut Vec<u8>) };
+        buffer.extend_from_slice(unsafe { std::slice::from_raw_parts(data, len) });
+    }));
+}
+
+fn copy_path(file_name: &str) -> anyhow::Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_nanos();
+    let copy = NEXT_COPY.fetch_add(1, Ordering::Relaxed);
+    Ok(std::env::temp_dir().join(format!("computer_{}_{}_{}_{}", std::process::id(), nanos, copy, file_name)))
+}
+
+pub struct LibraryPlugin {
+    update_fn: UpdateFn,
+    name: String,
+    key: String,
+    interval: Interval,
+    fault: Option<String>,
+    rejected_cmds: usize,
+    library: Option<libloading::Library>,
+    copy_path: PathBuf,
+}
+
+impl LibraryPlugin {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let file_name = path.file_name().ok_or(anyhow::anyhow!("no file name"))?.to_string_lossy();
+        let copy_path = copy_path(&file_name)?;
+        fs::copy(path, &copy_path)?;
+
+        let library = match unsafe { libloading::Library::new(&copy_path) } {
+            Ok(library) => library,
+            Err(err) => {
+                let _ = fs::remove_file(&copy_path);
+                return Err(err.into());
+            }
+        };
+        // From here on dropping the plugin unloads the library and removes the copy
+        let mut plugin = Self {
+            update_fn: failed_update,
+            name: String::new(),
+            key: format!("lib:{}", file_name),
+            interval: Interval::new(time::Duration::from_secs(1)),
+            fault: None,
+            rejected_cmds: 0,
+            library: Some(library),
+            copy_path,
+        };
+        let library = plugin.library.as_ref().unwrap();
+        let (abi_version, name_fn, update_interval_fn, update_fn) = unsafe {
+            (
+                *library.get::<extern "C" fn() -> u32>(PLUGIN_ABI_VERSION_SYMBOL)?,
+                *library.get::<NameFn>(PLUGIN_NAME_SYMBOL)?,
+                *library.get::<UpdateIntervalFn>(PLUGIN_UPDATE_INTERVAL_SYMBOL)?,
+                *library.get::<UpdateFn>(PLUGIN_UPDATE_SYMBOL)?,
+            )
+        };
+        let abi_version = abi_version();
+        if abi_version != PLUGIN_ABI_VERSION {
+            anyhow::bail!("plugin abi version {} is not {}", abi_version, PLUGIN_ABI_VERSION);
+        }
+
+        let mut name = vec![];
+        if !name_fn(write_bytes, &mut name as *mut Vec<u8> as *mut c_void) {
+            anyhow::bail!("name failed");
+        }
+        name.truncate(MAX_NAME_LENGTH);
+        plugin.name = String::from_utf8_lossy(&name).to_string();
+        plugin.interval = Interval::new(time::Duration::from_millis(update_interval_fn().max(1)));
+        plugin.update_fn = update_fn;
+        Ok(plugin)
+    }
+
+    fn call_update(&self, game_data: &GameData) -> anyhow::Result<Vec<GameCmd>> {
+        let game = serialize_bytes(&*game_data.game)?;
+        let user = serialize_bytes(&User::Player(game_data.player_id))?;
+        let mut output = vec![];
+        if !(self.update_fn)(game.as_ptr(), game.len(), user.as_ptr(), user.len(), write_bytes, &mut output as *mut Vec<u8> as *mut c_void) {
+            anyhow::bail!("update failed");
+        }
+        Ok(deserialize_bytes(&output)?)
+    }
+}
+
+extern "C" fn failed_update(_: *const u8, _: usize, _: *const u8, _: usize, _: WriteBytes, _: *mut c_void) -> bool {
+    false
+}
+
+impl Drop for LibraryPlugin {
+    fn drop(&mut self) {
+        // Unmapped first, so the copy is not removed while in use
+        drop(self.library.take());
+        let _ = fs::remove_file(&self.copy_path);
+    }
+}
+
+impl Plugin for LibraryPlugin {
+    fn name(&self) -> String {
+        format!("[lib] {}", self.name)
+    }
+
+    fn handoff_key(&self) -> String {
+        self.key.clone()
+    }
+
+    fn update(&mut self, game_data: &mut GameData) {
+        if self.fault.is_some() {
+            return;
+        }
+        let cmds = match self.call_update(game_data) {
+            Ok(cmds) => cmds,
+            Err(err) => {
+                self.fault = Some(err.to_string());
+                return;
+            }
+        };
+        for cmd in cmds {
+            if game_data.try_execute_cmd(cmd).is_err() {
+                self.rejected_cmds += 1;
+            }
+        }
+    }
+
+    fn update_ui(&mut self, ui: &mut egui::Ui) {
+        if let Some(fault) = &self.fault {
+            ui.colored_label(egui::Color32::RED, format!("Stopped: {}", fault));
+            return;
+        }
+        if self.rejected_cmds > 0 {
+            ui.colored_label(egui::Color32::YELLOW, format!("Rejected commands: {}", self.rejected_cmds));
+        }
+        ui.label("Running");
+    }
+
+    fn update_interval(&mut self) -> &mut Interval {
+        &mut self.interval
+    }
+}
+
+struct LibraryFile {
+    plugin: Result<LibraryPlugin, String>,
+    enabled: bool,
+    unloaded: bool,
+}
+
+pub struct PluginLibraries {
+    dir: WatchedDir,
+    interval: Interval,
+    files: BTreeMap<PathBuf, LibraryFile>,
+    error: Option<String>,
+}
+
+impl PluginLibraries {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            dir: WatchedDir::new(path),
+            interval: Interval::new(time::Duration::from_secs(2)),
+            files: BTreeMap::new(),
+            error: None,
+        }
+    }
+
+    pub fn refresh(&mut self) {
+        if !self.interval.check() {
+            return;
+        }
+        self.error = None;
+        if let Err(err) = self.scan() {
+            self.error = Some(format!("Could not read {:?}: {}", self.dir.path(), err));
+        }
+    }
+
+    fn scan(&mut self) -> anyhow::Result<()> {
+        let changes = self.dir.scan(|path| has_extension(path, std::env::consts::DLL_EXTENSION))?;
+        for library_path in changes.changed {
+            if self.files.get(&library_path).is_some_and(|file| file.unloaded) {
+                continue;
+            }
+            let enabled = self.files.remove(&library_path).is_some_and(|file| file.enabled);
+            let plugin = LibraryPlugin::load(&library_path).map_err(|err| err.to_string());
+            self.files.insert(library_path, LibraryFile { plugin, enabled, unloaded: false });
+        }
+        for library_path in changes.removed {
+            self.files.remove(&library_path);
+        }
+        Ok(())
+    }
+
+    pub fn plugins_mut(&mut self) -> impl Iterator<Item = (&mut bool, &mut (dyn Plugin + 'static))> {
+        self.files.values_mut().filter_map(|file| {
+            let plugin = file.plugin.as_mut().ok()?;
+            Some((&mut file.enabled, plugin as &mut (dyn Plugin + 'static)))
+        })
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        let mut reload = vec![];
+        for (path, file) in &mut self.files {
+            let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            ui.horizontal(|ui| {
+                match &file.plugin {
+                    Ok(plugin) => {
+                        ui.checkbox(&mut file.enabled, plugin.name());
+                    }
+                    Err(_) if file.unloaded => {
+                        ui.weak(format!("{} (unloaded)", file_name));
+                    }
+                    Err(err) => {
+                        ui.colored_label(egui::Color32::RED, format!("{}: {}", file_name, err));
+                    }
+                }
+                if ui.small_button("Reload").clicked() {
+                    reload.push(path.clone());
+                }
+                if file.plugin.is_ok() && ui.small_button("Unload").clicked() {
+                    file.plugin = Err("unloaded".into());
+                    file.enabled = false;
+                    file.unloaded = true;
+                }
+            });
+        }
+        if reload.is_empty() {
+            return;
+        }
+        for path in reload {
+            if let Some(file) = self.files.get_mut(&path) {
+                file.unloaded = false;
+            }
+            self.dir.forget(&path);
+        }
+        if let Err(err) = self.scan() {
+            self.error = Some(format!("Could not read {:?}: {}", self.dir.path(), err));
+        }
+    }
+}
@@ -5,10 +5,22 @@ mod build_spacecrafts;
 // mod spacecraft_control;
 mod spacecraft_construction;
 mod spacecraft_controlv2;
+mod structure_optimizer;
+mod scripting;
+mod wasm_sandbox;
+mod telemetry;
+mod debug_overlay;
+mod minimap;
 
-pub use spacecraft_controlv2::{SpacecraftControl, SpacecraftState};
-// pub use build_spacecrafts::BuildSpacecrafts;
+pub use spacecraft_controlv2::{SpacecraftControl, SpacecraftState, SpacecraftHealth, state_combo_box};
+pub use build_spacecrafts::BuildSpacecrafts;
 pub use spacecraft_construction::SpacecraftConstruction;
+pub use structure_optimizer::StructureOptimizer;
+pub use scripting::ScriptRunner;
+pub use wasm_sandbox::WasmSandbox;
+pub use telemetry::Telemetry;
+pub use debug_overlay::DebugOverlay;
+pub use minimap::Minimap;
 
 use super::*;
 
@@ -17,7 +29,22 @@ use utils::{predictive_shoot_at, shoot_at, fly_to, improved_fly_to};
 pub trait Plugin {
     fn update(&mut self, game_data: &mut GameData) {}
     fn update_ui(&mut self, ui: &mut egui::Ui) {}
+    /// Called every frame while the plugin is enabled, also when its window is collapsed
+    fn paint(&mut self, _ctx: &egui::Context) {}
     fn name(&self) -> String;
     fn update_interval(&mut self) -> &mut Interval;
+    fn handoff_key(&self) -> String {
+        self.name()
+    }
+    /// State handed to the next build along with its version, `None` if the plugin starts fresh
+    fn save_state(&self) -> Option<(u32, Vec<u8>)> {
+        None
+    }
+    /// Restores state saved by a possibly older build, migrating from `version` when needed
+    fn load_state(&mut self, _version: u32, _data: &[u8]) -> anyhow::Result<()> {
+        Ok(())
+    }
+    /// Called once the state is exported and the build is about to be unloaded
+    fn unload(&mut self) {}
 }
 
@@ -1,97 +1,199 @@
+use std::{path::PathBuf, sync::atomic::{AtomicUsize, Ordering}};
+
 use super::*;
 
+use structure_loader::StructureLoader;
+
+static NEXT_INSTANCE: AtomicUsize = AtomicUsize::new(1);
 
 #[derive(Serialize, Deserialize)]
+struct ProductionHandoff {
+    structure_name: String,
+    tag: String,
+    max_parallel: usize,
+    max_total: Option<usize>,
+    deploy_finished: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct ProductionCounts {
+    pub alive: usize,
+    pub building: usize,
+    pub queued: usize,
+}
+
+#[derive(Serialize)]
 pub struct BuildSpacecrafts {
-    structure: SpacecraftStructure,
+    instance: usize,
+    structure_loader: StructureLoader,
+    structure_name: String,
     tag: String,
     max_parallel: usize,
     max_total: Option<usize>,
+    deploy_finished: bool,
+    counts: ProductionCounts,
+    status: Option<String>,
     pub interval: Interval
 }
 
 impl BuildSpacecrafts {
-    pub fn new(
-        structure: SpacecraftStructure,
-        tag: String,
-        max_parallel: usize,
-        max_total: Option<usize>,
-    ) -> Self {
-        if !structure.valid() {
-            panic!("structure {:?} non valid, quitting", structure);
-        }
+    /// Production plugins are added at runtime and recreated on reload
+    pub const HANDOFF_PREFIX: &'static str = "production #";
+
+    pub fn new(spacecraft_structures_path: PathBuf) -> Self {
         Self {
-            structure,
-            tag,
-            max_parallel,
-            max_total,
+            instance: NEXT_INSTANCE.fetch_add(1, Ordering::Relaxed),
+            structure_loader: StructureLoader::new(spacecraft_structures_path),
+            structure_name: String::new(),
+            tag: String::new(),
+            max_parallel: 1,
+            max_total: None,
+            deploy_finished: true,
+            counts: Default::default(),
+            status: None,
             interval: Interval::new(time::Duration::from_secs(2))
         }
     }
-   
+
+    fn count(&self, game_data: &GameData) -> ProductionCounts {
+        let mut counts = ProductionCounts::default();
+        counts.alive = game_data.my_spacecrafts().values().filter(|spacecraft| spacecraft.tags.contains(&self.tag)).count();
+        for star_base in game_data.my_star_bases().values() {
+            for hangar in &star_base.hangars {
+                for (position, structure) in hangar.building_queue.iter().enumerate() {
+                    if !structure.tags.contains(&self.tag) {
+                        continue;
+                    }
+                    if position == 0 {
+                        counts.building += 1;
+                    } else {
+                        counts.queued += 1;
+                    }
+                }
+            }
+        }
+        counts
+    }
 }
 
 impl Plugin for BuildSpacecrafts {
 
     fn name(&self) -> String {
-        format!("Build {}", self.tag)
+        format!("Build {} #{}", self.tag, self.instance)
     }
     fn update(&mut self, game_data: &mut GameData) {
-        let star_bases = game_data.my_star_bases();
-        let mut remaining_parallel = self.max_parallel;
-
-        let mut hangars_available = vec![];
-
-        let mut total = 0;
-        for (id, spacecraft) in game_data.my_spacecrafts() {
-            if spacecraft.tags.contains(&self.tag.to_string()) {
-                total += 1;
-            }
+        self.structure_loader.refresh();
+        let Some(structure) = self.structure_loader.structure(&self.structure_name).cloned() else {
+            self.status = Some("Choose a structure".into());
+            return;
+        };
+        if self.tag.is_empty() {
+            self.tag = structure.tags.first().cloned().unwrap_or_default();
         }
-
-        if let Some(max_total) = self.max_total {
-            if total >= max_total {
-                return;
-            }
+        if !structure.tags.contains(&self.tag) {
+            self.status = Some(format!("Structure does not carry the tag {}", self.tag));
+            return;
         }
+        self.status = None;
 
-
-        for (id, star_base) in &star_bases {
-            for (index, hangar) in star_base.hangars.iter().enumerate() {
-                for spacecraft_structure in &hangar.building_queue {
-                    if spacecraft_structure.tags.contains(&self.tag.to_string()) && remaining_parallel > 0 {
-                        if remaining_parallel > 0 {
-                            remaining_parallel -= 1;
-                        }
-                        if hangar.build_finished() {
-                            game_data.execute_cmd(GameCmd::DeploySpacecraft(*id, index));
-                        }
+        if self.deploy_finished {
+            for (id, star_base) in game_data.my_star_bases() {
+                for (index, hangar) in star_base.hangars.iter().enumerate() {
+                    let ours = hangar.building_queue.first().is_some_and(|x| x.tags.contains(&self.tag));
+                    if ours && hangar.build_finished() {
+                        game_data.execute_cmd(GameCmd::DeploySpacecraft(id, index));
                     }
                 }
-                if hangar.building_queue.is_empty() {
-                    hangars_available.push((*id, index, hangar));
-                }
             }
         }
 
-        for (star_base_id, hangar_index, _) in hangars_available.iter().take(remaining_parallel) {
-            if game_data.player().has_materials(&self.structure.materials()) {
-                game_data.execute_cmd(GameCmd::BuildSpacecraft(
-                    *star_base_id,
-                    self.structure.clone(),
-                    *hangar_index,
-                ));
+        self.counts = self.count(game_data);
+        let in_production = self.counts.building + self.counts.queued;
+        let mut remaining_parallel = self.max_parallel.saturating_sub(in_production);
+        if let Some(max_total) = self.max_total {
+            remaining_parallel = remaining_parallel.min(max_total.saturating_sub(self.counts.alive + in_production));
+        }
+
+        let hangars_available = game_data.my_star_bases().into_iter().flat_map(|(id, star_base)| {
+            star_base.hangars.into_iter().enumerate()
+                .filter(|(_, hangar)| hangar.building_queue.is_empty())
+                .map(move |(index, _)| (id, index))
+        }).collect::<Vec<_>>();
+
+        for (star_base_id, hangar_index) in hangars_available.into_iter().take(remaining_parallel) {
+            if !game_data.player().has_materials(&structure.materials()) {
+                break;
             }
+            game_data.execute_cmd(GameCmd::BuildSpacecraft(
+                star_base_id,
+                structure.clone(),
+                hangar_index,
+            ));
         }
+        self.counts = self.count(game_data);
     }
     fn update_ui(&mut self, ui: &mut egui::Ui) {
-        ui.label(format!("Max parallel: {}", self.max_parallel));
-        ui.label(format!("Max total: {:?}", self.max_total));
-        ui.label(format!("Tag: {}", self.tag));
-        ui.label(format!("Structure: {:?}", self.structure));
+        egui::ComboBox::from_label("Structure")
+            .selected_text(&self.structure_name)
+            .show_ui(ui, |ui| {
+                for (name, structure) in self.structure_loader.structures() {
+                    if ui.selectable_label(self.structure_name == *name, name).clicked() {
+                        self.structure_name = name.clone();
+                        self.tag = structure.tags.first().cloned().unwrap_or_default();
+                    }
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("Tag");
+            ui.text_edit_singleline(&mut self.tag);
+        });
+        ui.add(egui::DragValue::new(&mut self.max_parallel).prefix("Max parallel: "));
+        let mut limited = self.max_total.is_some();
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut limited, "Max total");
+            let mut max_total = self.max_total.unwrap_or(self.counts.alive.max(1));
+            if limited {
+                ui.add(egui::DragValue::new(&mut max_total));
+            }
+            self.max_total = limited.then_some(max_total);
+        });
+        ui.checkbox(&mut self.deploy_finished, "deploy finished");
+        ui.label(format!("Alive: {}, building: {}, queued: {}", self.counts.alive, self.counts.building, self.counts.queued));
+        if let Some(status) = &self.status {
+            ui.colored_label(egui::Color32::YELLOW, status);
+        }
+        self.structure_loader.report_ui(ui);
     }
 
     fn update_interval(&mut self) -> &mut Interval {
         &mut self.interval 
     }
-}
\ No newline at end of file
+
+    fn handoff_key(&self) -> String {
+        format!("{}{}", Self::HANDOFF_PREFIX, self.instance)
+    }
+
+    fn save_state(&self) -> Option<(u32, Vec<u8>)> {
+        let handoff = ProductionHandoff {
+            structure_name: self.structure_name.clone(),
+            tag: self.tag.clone(),
+            max_parallel: self.max_parallel,
+            max_total: self.max_total,
+            deploy_finished: self.deploy_finished,
+        };
+        Some((1, serialize_bytes(&handoff).ok()?))
+    }
+
+    fn load_state(&mut self, version: u32, data: &[u8]) -> anyhow::Result<()> {
+        if version != 1 {
+            anyhow::bail!("unknown state version {}", version);
+        }
+        let handoff: ProductionHandoff = deserialize_bytes(data)?;
+        self.structure_name = handoff.structure_name;
+        self.tag = handoff.tag;
+        self.max_parallel = handoff.max_parallel;
+        self.max_total = handoff.max_total;
+        self.deploy_finished = handoff.deploy_finished;
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::{BTreeSet, HashSet};
+
+use super::*;
+
+use debug_draw::{Layer, Shape};
+
+pub struct DebugOverlay {
+    use_host_camera: bool,
+    center: Vec2,
+    pixels_per_unit: f32,
+    flip_y: bool,
+    /// Seconds of travel a velocity arrow shows
+    velocity_scale: f32,
+    max_age: f32,
+    hidden_layers: HashSet<Layer>,
+    hidden_spacecrafts: HashSet<String>,
+    spacecrafts: BTreeSet<String>,
+    interval: Interval,
+}
+
+impl DebugOverlay {
+    pub fn new() -> Self {
+        Self {
+            use_host_camera: true,
+            center: Vec2::ZERO,
+            pixels_per_unit: 1.,
+            flip_y: true,
+            velocity_scale: 1.,
+            max_age: 2.,
+            hidden_layers: HashSet::new(),
+            hidden_spacecrafts: HashSet::new(),
+            spacecrafts: BTreeSet::new(),
+            interval: Interval::new(time::Duration::from_secs(1)),
+        }
+    }
+
+    fn camera(&self) -> (Vec2, f32) {
+        if self.use_host_camera && let Some(camera) = *debug_draw::HOST_CAMERA.lock().unwrap() {
+            return camera;
+        }
+        (self.center, self.pixels_per_unit)
+    }
+
+    fn paint_drawings(&mut self, ctx: &egui::Context) {
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("debug overlay")));
+        let screen_center = ctx.screen_rect().center();
+        let (center, pixels_per_unit) = self.camera();
+        let y = if self.flip_y { -1. } else { 1. };
+        let to_screen = |position: Vec2| {
+            let offset = (position - center) * pixels_per_unit;
+            screen_center + egui::vec2(offset.x, offset.y * y)
+        };
+
+        self.spacecrafts.clear();
+        for ((spacecraft, layer), drawing) in debug_draw::drawings(self.max_age) {
+            self.spacecrafts.insert(spacecraft.clone());
+            if self.hidden_layers.contains(&layer) || self.hidden_spacecrafts.contains(&spacecraft) {
+                continue;
+            }
+            let stroke = egui::Stroke::new(1.5, layer.color());
+            for shape in drawing.shapes {
+                match shape {
+                    Shape::Line(from, to) => painter.line_segment([to_screen(from), to_screen(to)], stroke),
+                    Shape::Velocity(from, velocity) => {
+                        let origin = to_screen(from);
+                        painter.arrow(origin, to_screen(from + velocity * self.velocity_scale) - origin, stroke);
+                    }
+                    Shape::Circle(position, radius) => {
+                        painter.circle_stroke(to_screen(position), (radius * pixels_per_unit).max(3.), stroke);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Plugin for DebugOverlay {
+    fn name(&self) -> String {
+        "debug overlay".into()
+    }
+
+    fn update_ui(&mut self, ui: &mut egui::Ui) {
+        use strum::IntoEnumIterator;
+
+        let host_camera = debug_draw::HOST_CAMERA.lock().unwrap().is_some();
+        ui.add_enabled(host_camera, egui::Checkbox::new(&mut self.use_host_camera, "Use host camera"));
+        if !(host_camera && self.use_host_camera) {
+            ui.horizontal(|ui| {
+                ui.label("Center");
+                ui.add(egui::DragValue::new(&mut self.center.x).prefix("x: "));
+                ui.add(egui::DragValue::new(&mut self.center.y).prefix("y: "));
+            });
+            ui.add(egui::Slider::new(&mut self.pixels_per_unit, 0.01..=20.).logarithmic(true).text("pixels per unit"));
+            ui.checkbox(&mut self.flip_y, "y axis points up");
+        }
+        ui.add(egui::Slider::new(&mut self.velocity_scale, 0.1..=10.).text("velocity arrow seconds"));
+        ui.add(egui::Slider::new(&mut self.max_age, 0.5..=10.).text("keep drawings for seconds"));
+
+        ui.horizontal(|ui| {
+            for layer in Layer::iter() {
+                let mut shown = !self.hidden_layers.contains(&layer);
+                if ui.checkbox(&mut shown, egui::RichText::new(layer.to_string()).color(layer.color())).changed() {
+                    if shown {
+                        self.hidden_layers.remove(&layer);
+                    } else {
+                        self.hidden_layers.insert(layer);
+                    }
+                }
+            }
+        });
+        ui.collapsing("Spacecrafts", |ui| {
+            for spacecraft in &self.spacecrafts {
+                let mut shown = !self.hidden_spacecrafts.contains(spacecraft);
+                if ui.checkbox(&mut shown, spacecraft).changed() {
+                    if shown {
+                        self.hidden_spacecrafts.remove(spacecraft);
+                    } else {
+                        self.hidden_spacecrafts.insert(spacecraft.clone());
+                    }
+                }
+            }
+        });
+    }
+
+    fn paint(&mut self, ctx: &egui::Context) {
+        debug_draw::keep_alive();
+        self.paint_drawings(ctx);
+    }
+
+    fn update_interval(&mut self) -> &mut Interval {
+        &mut self.interval
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+
+use super::*;
+
+const PICK_RADIUS: f32 = 8.;
+
+#[derive(Debug, Clone, PartialEq)]
+enum MapObjectKind {
+    MySpacecraft,
+    EnemySpacecraft,
+    MyStarBase,
+    EnemyStarBase,
+    Asteroid(Material),
+}
+
+#[derive(Debug, Clone)]
+struct MapObject {
+    id: GameObjectId,
+    kind: MapObjectKind,
+    position: Vec2,
+}
+
+impl MapObject {
+    fn color(&self) -> egui::Color32 {
+        match &self.kind {
+            MapObjectKind::MySpacecraft | MapObjectKind::MyStarBase => egui::Color32::from_rgb(80, 160, 255),
+            MapObjectKind::EnemySpacecraft | MapObjectKind::EnemyStarBase => egui::Color32::from_rgb(255, 80, 80),
+            MapObjectKind::Asteroid(material) => material_color(material),
+        }
+    }
+
+    fn is_enemy(&self) -> bool {
+        matches!(self.kind, MapObjectKind::EnemySpacecraft | MapObjectKind::EnemyStarBase)
+    }
+}
+
+fn material_color(material: &Material) -> egui::Color32 {
+    let hash = format!("{:?}", material).bytes().fold(7u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32));
+    egui::epaint::Hsva::new((hash % 360) as f32 / 360., 0.5, 0.8, 1.).into()
+}
+
+pub struct Minimap {
+    objects: Vec<MapObject>,
+    center: Vec2,
+    /// Pixels per world unit
+    zoom: f32,
+    height: f32,
+    selected: HashSet<GameObjectId>,
+    box_start: Option<egui::Pos2>,
+    state: SpacecraftState,
+    orders: Vec<(GameObjectId, SpacecraftState)>,
+    last_order: Option<String>,
+    behaviour_names: Vec<String>,
+    interval: Interval,
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        Self {
+            objects: vec![],
+            center: Vec2::ZERO,
+            zoom: 0.1,
+            height: 300.,
+            selected: HashSet::new(),
+            box_start: None,
+            state: SpacecraftState::Idle,
+            orders: vec![],
+            last_order: None,
+            behaviour_names: vec![],
+            interval: Interval::new(time::Duration::from_millis(200)),
+        }
+    }
+
+    fn fit(&mut self, size: egui::Vec2) {
+        let Some(first) = self.objects.first() else {
+            return;
+        };
+        let (mut min, mut max) = (first.position, first.position);
+        for object in &self.objects {
+            min = min.min(object.position);
+            max = max.max(object.position);
+        }
+        self.center = (min + max) / 2.;
+        let extent = (max - min).max(Vec2::splat(1.));
+        self.zoom = (size.x / extent.x).min(size.y / extent.y) * 0.9;
+    }
+
+    fn order(&mut self, state: SpacecraftState) {
+        self.last_order = Some(format!("{:?} for {} spacecraft", state, self.selected.len()));
+        for id in &self.selected {
+            self.orders.push((*id, state.clone()));
+        }
+    }
+
+    fn map_ui(&mut self, ui: &mut egui::Ui) {
+        let size = egui::vec2(ui.available_width(), self.height);
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::click_and_drag());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0., egui::Color32::from_gray(15));
+
+        let (center, zoom) = (self.center, self.zoom);
+        let to_screen = |position: Vec2| {
+            let offset = (position - center) * zoom;
+            rect.center() + egui::vec2(offset.x, -offset.y)
+        };
+        let to_world = |position: egui::Pos2| {
+            let offset = position - rect.center();
+            center + Vec2::new(offset.x, -offset.y) / zoom
+        };
+        let pick = |position: egui::Pos2, filter: &dyn Fn(&MapObject) -> bool| {
+            self.objects.iter()
+                .filter(|object| filter(object))
+                .map(|object| (object, to_screen(object.position).distance(position)))
+                .filter(|(_, distance)| *distance < PICK_RADIUS)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(object, _)| object.clone())
+        };
+
+        for object in &self.objects {
+            let position = to_screen(object.position);
+            if !rect.contains(position) {
+                continue;
+            }
+            match object.kind {
+                MapObjectKind::MyStarBase | MapObjectKind::EnemyStarBase => {
+                    painter.rect_filled(egui::Rect::from_center_size(position, egui::vec2(8., 8.)), 0., object.color());
+                }
+                MapObjectKind::Asteroid(_) => {
+                    painter.circle_filled(position, 2.5, object.color());
+                }
+                MapObjectKind::MySpacecraft | MapObjectKind::EnemySpacecraft => {
+                    painter.circle_filled(position, 3.5, object.color());
+                }
+            }
+            if self.selected.contains(&object.id) {
+                painter.circle_stroke(position, 6., egui::Stroke::new(1., egui::Color32::WHITE));
+            }
+        }
+
+        if response.hovered() {
+            let scroll = ui.input(|input| input.scroll_delta.y);
+            if scroll != 0. {
+                // Zoom around the cursor so the point under it stays in place
+                let hover = response.hover_pos().map(to_world).unwrap_or(center);
+                let factor = (scroll * 0.002).exp();
+                self.zoom = (self.zoom * factor).clamp(1e-4, 100.);
+                self.center = hover + (self.center - hover) / factor;
+            }
+            if let Some(hover_pos) = response.hover_pos() && let Some(object) = pick(hover_pos, &|_| true) {
+                response.clone().on_hover_text(format!("{:?} {}", object.kind, object.id));
+            }
+        }
+
+        if response.dragged_by(egui::PointerButton::Middle) {
+            let delta = response.drag_delta();
+            self.center -= Vec2::new(delta.x, -delta.y) / self.zoom;
+        }
+
+        let shift = ui.input(|input| input.modifiers.shift);
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            self.box_start = response.interact_pointer_pos();
+        }
+        if let Some(start) = self.box_start && let Some(current) = response.interact_pointer_pos() {
+            let selection = egui::Rect::from_two_pos(start, current);
+            painter.rect_stroke(selection, 0., egui::Stroke::new(1., egui::Color32::WHITE));
+            if response.drag_released_by(egui::PointerButton::Primary) {
+                if !shift {
+                    self.selected.clear();
+                }
+                for object in &self.objects {
+                    if object.kind == MapObjectKind::MySpacecraft && selection.contains(to_screen(object.position)) {
+                        self.selected.insert(object.id);
+                    }
+                }
+                self.box_start = None;
+            }
+        }
+        if !response.dragged() {
+            self.box_start = None;
+        }
+
+        if response.clicked() && let Some(position) = response.interact_pointer_pos() {
+            if !shift {
+                self.selected.clear();
+            }
+            if let Some(object) = pick(position, &|object| object.kind == MapObjectKind::MySpacecraft) {
+                self.selected.insert(object.id);
+            }
+        }
+
+        if response.secondary_clicked() && !self.selected.is_empty() && let Some(position) = response.interact_pointer_pos() {
+            match pick(position, &MapObject::is_enemy) {
+                Some(enemy) => self.order(SpacecraftState::AttackTarget(enemy.id)),
+                None => self.order(SpacecraftState::MoveTo(to_world(position))),
+            }
+        }
+    }
+}
+
+impl Plugin for Minimap {
+    fn name(&self) -> String {
+        "minimap".into()
+    }
+
+    fn update(&mut self, game_data: &mut GameData) {
+        for (id, state) in self.orders.drain(..) {
+            game_data.assign_state(id, state);
+        }
+        self.behaviour_names = game_data.shared.behaviour_names.clone();
+
+        self.objects = game_data.game_objects.iter().filter_map(|(id, game_object)| {
+            let (kind, position) = match game_object {
+                GameObject::Spacecraft(spacecraft) if spacecraft.owner == game_data.player_id => (MapObjectKind::MySpacecraft, spacecraft.body.position),
+                GameObject::Spacecraft(spacecraft) => (MapObjectKind::EnemySpacecraft, spacecraft.body.position),
+                GameObject::StarBase(star_base) if star_base.owner == game_data.player_id => (MapObjectKind::MyStarBase, star_base.body.position),
+                GameObject::StarBase(star_base) => (MapObjectKind::EnemyStarBase, star_base.body.position),
+                GameObject::Asteroid(asteroid) => (MapObjectKind::Asteroid(asteroid.material.clone()), asteroid.body.position),
+                _ => return None,
+            };
+            Some(MapObject { id: *id, kind, position })
+        }).collect();
+
+        let objects = &self.objects;
+        self.selected.retain(|id| objects.iter().any(|object| object.id == *id));
+    }
+
+    fn update_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Fit").clicked() {
+                self.fit(egui::vec2(ui.available_width(), self.height));
+            }
+            ui.add(egui::Slider::new(&mut self.height, 150.0..=800.).text("height"));
+        });
+        ui.weak("Click or drag to select, shift to add, middle drag to pan, scroll to zoom, right click to move or attack");
+
+        self.map_ui(ui);
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{} selected", self.selected.len()));
+            state_combo_box(ui, "State", &mut self.state, &self.behaviour_names);
+            if ui.add_enabled(!self.selected.is_empty(), egui::Button::new("Assign")).clicked() {
+                self.order(self.state.clone());
+            }
+        });
+        if let Some(last_order) = &self.last_order {
+            ui.weak(last_order);
+        }
+    }
+
+    fn update_interval(&mut self) -> &mut Interval {
+        &mut self.interval
+    }
+}
@@ -0,0 +1,349 @@
+use std::{cell::{Cell, RefCell}, collections::VecDeque, fs, path::PathBuf, rc::Rc};
+
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, AST};
+
+use super::*;
+
+use utils::deactivate_weapons;
+use watched_dir::{WatchedDir, has_extension};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+struct ScriptHost {
+    game_data: *mut GameData<'static>,
+    log: VecDeque<String>,
+}
+
+fn with_game<T>(host: &RefCell<ScriptHost>, f: impl FnOnce(&mut GameData) -> ScriptResult<T>) -> ScriptResult<T> {
+    // Copied out so the host is not borrowed while `f` runs, e.g. when it prints
+    let game_data = host.borrow().game_data;
+    if game_data.is_null() {
+        return Err("game is only accessible while scripts are updated".into());
+    }
+    f(unsafe { &mut *game_data })
+}
+
+/// Hands the game to the script host until dropped, also when a script call unwinds
+struct GameAccess<'a> {
+    host: &'a RefCell<ScriptHost>,
+    _game_data: std::marker::PhantomData<&'a mut GameData<'a>>,
+}
+
+impl<'a> GameAccess<'a> {
+    fn new(host: &'a RefCell<ScriptHost>, game_data: &'a mut GameData) -> Self {
+        host.borrow_mut().game_data = game_data as *mut GameData as *mut GameData<'static>;
+        Self { host, _game_data: std::marker::PhantomData }
+    }
+}
+
+impl Drop for GameAccess<'_> {
+    fn drop(&mut self) {
+        self.host.borrow_mut().game_data = std::ptr::null_mut();
+    }
+}
+
+fn my_spacecraft(game_data: &GameData, id: GameObjectId) -> ScriptResult<Spacecraft> {
+    match game_data.game_objects.get(&id) {
+        Some(GameObject::Spacecraft(spacecraft)) if spacecraft.owner == game_data.player_id => Ok(spacecraft.clone()),
+        _ => Err(format!("{} is not one of my spacecraft", id).into()),
+    }
+}
+
+fn body(game_data: &GameData, id: GameObjectId) -> ScriptResult<GameObjectBody> {
+    game_data.game_object_body(id).ok_or_else(|| format!("no game object {}", id).into())
+}
+
+fn optional_id(id: Option<GameObjectId>) -> Dynamic {
+    id.map(Dynamic::from).unwrap_or(Dynamic::UNIT)
+}
+
+fn register_api(engine: &mut Engine, host: &Rc<RefCell<ScriptHost>>) {
+    engine.register_type_with_name::<GameObjectId>("Id")
+        .register_fn("to_string", |id: &mut GameObjectId| id.to_string())
+        .register_fn("==", |a: GameObjectId, b: GameObjectId| a == b)
+        .register_fn("!=", |a: GameObjectId, b: GameObjectId| a != b);
+
+    engine.register_type_with_name::<Vec2>("Vec2")
+        .register_fn("vec2", |x: f32, y: f32| Vec2::new(x, y))
+        .register_get("x", |v: &mut Vec2| v.x)
+        .register_get("y", |v: &mut Vec2| v.y)
+        .register_fn("+", |a: Vec2, b: Vec2| a + b)
+        .register_fn("-", |a: Vec2, b: Vec2| a - b)
+        .register_fn("*", |a: Vec2, b: f32| a * b)
+        .register_fn("length", |v: &mut Vec2| v.length())
+        .register_fn("distance", |a: Vec2, b: Vec2| a.distance(b))
+        .register_fn("to_string", |v: &mut Vec2| format!("({:.1}, {:.1})", v.x, v.y));
+
+    let log = host.clone();
+    engine.on_print(move |text| {
+        let mut host = log.borrow_mut();
+        host.log.push_back(text.to_string());
+        if host.log.len() > 100 {
+            host.log.pop_front();
+        }
+    });
+
+    let h = host.clone();
+    engine.register_fn("my_spacecrafts", move || -> ScriptResult<Array> {
+        with_game(&h, |game_data| Ok(game_data.my_spacecrafts().into_keys().map(Dynamic::from).collect()))
+    });
+    let h = host.clone();
+    engine.register_fn("my_star_bases", move || -> ScriptResult<Array> {
+        with_game(&h, |game_data| Ok(game_data.my_star_bases().into_keys().map(Dynamic::from).collect()))
+    });
+    let h = host.clone();
+    engine.register_fn("tags", move |id: GameObjectId| -> ScriptResult<Array> {
+        with_game(&h, |game_data| Ok(my_spacecraft(game_data, id)?.tags.into_iter().map(Dynamic::from).collect()))
+    });
+    let h = host.clone();
+    engine.register_fn("position", move |id: GameObjectId| -> ScriptResult<Vec2> {
+        with_game(&h, |game_data| Ok(body(game_data, id)?.position))
+    });
+    let h = host.clone();
+    engine.register_fn("velocity", move |id: GameObjectId| -> ScriptResult<Vec2> {
+        with_game(&h, |game_data| Ok(body(game_data, id)?.velocity))
+    });
+    let h = host.clone();
+    engine.register_fn("closest_enemy", move |position: Vec2| -> ScriptResult<Dynamic> {
+        with_game(&h, |game_data| Ok(optional_id(game_data.closest_enemy_target(&position).map(|x| x.0))))
+    });
+    let h = host.clone();
+    engine.register_fn("closest_asteroid", move |position: Vec2| -> ScriptResult<Dynamic> {
+        with_game(&h, |game_data| Ok(optional_id(game_data.closest_asteroid(&position).map(|x| x.0))))
+    });
+    let h = host.clone();
+    engine.register_fn("closest_my_star_base", move |position: Vec2| -> ScriptResult<Dynamic> {
+        with_game(&h, |game_data| Ok(optional_id(game_data.closest_my_star_base(&position).map(|x| x.0))))
+    });
+    let h = host.clone();
+    engine.register_fn("material", move |name: &str| -> ScriptResult<f32> {
+        with_game(&h, |game_data| {
+            Ok(game_data.player().materials.iter()
+                .find(|(material, _)| format!("{:?}", material).eq_ignore_ascii_case(name))
+                .map(|(_, amount)| *amount)
+                .unwrap_or(0.))
+        })
+    });
+
+    let h = host.clone();
+    engine.register_fn("fly_to", move |id: GameObjectId, target: GameObjectId| -> ScriptResult<()> {
+        with_game(&h, |game_data| {
+            let spacecraft = my_spacecraft(game_data, id)?;
+            let target = body(game_data, target)?;
+            game_data.execute_cmds(improved_fly_to((&id, &spacecraft), target));
+            Ok(())
+        })
+    });
+    let h = host.clone();
+    engine.register_fn("move_to", move |id: GameObjectId, waypoint: Vec2| -> ScriptResult<()> {
+        with_game(&h, |game_data| {
+            let spacecraft = my_spacecraft(game_data, id)?;
+            let mut target = spacecraft.body.clone();
+            target.position = waypoint;
+            target.velocity = Vec2::ZERO;
+            game_data.execute_cmds(improved_fly_to((&id, &spacecraft), target));
+            Ok(())
+        })
+    });
+    let h = host.clone();
+    engine.register_fn("shoot_at", move |id: GameObjectId, target: GameObjectId| -> ScriptResult<()> {
+        with_game(&h, |game_data| {
+            let spacecraft = my_spacecraft(game_data, id)?;
+            let target = body(game_data, target)?;
+            game_data.execute_cmds(predictive_shoot_at((&id, &spacecraft), target));
+            Ok(())
+        })
+    });
+    let h = host.clone();
+    engine.register_fn("stop_shooting", move |id: GameObjectId| -> ScriptResult<()> {
+        with_game(&h, |game_data| {
+            let spacecraft = my_spacecraft(game_data, id)?;
+            game_data.execute_cmds(deactivate_weapons((&id, &spacecraft)));
+            Ok(())
+        })
+    });
+    let h = host.clone();
+    engine.register_fn("execute_cmd", move |cmd: Dynamic| -> ScriptResult<()> {
+        let cmd: GameCmd = rhai::serde::from_dynamic(&cmd)?;
+        with_game(&h, |game_data| Ok(game_data.try_execute_cmd(cmd)?))
+    });
+}
+
+struct Script {
+    name: String,
+    ast: Result<AST, String>,
+    state: Dynamic,
+    initialized: bool,
+    enabled: bool,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct OperationBudget {
+    limit: Cell<u64>,
+    used: Cell<u64>,
+    /// Used so far by the running call, the engine counts every call from zero
+    call: Cell<u64>,
+}
+
+impl OperationBudget {
+    fn start_tick(&self, limit: u64) {
+        self.limit.set(limit);
+        self.used.set(0);
+        self.call.set(0);
+    }
+
+    fn end_call(&self) {
+        self.used.set(self.used.get() + self.call.take());
+    }
+}
+
+pub struct ScriptRunner {
+    dir: WatchedDir,
+    engine: Engine,
+    host: Rc<RefCell<ScriptHost>>,
+    scripts: BTreeMap<PathBuf, Script>,
+    budget: Rc<OperationBudget>,
+    operation_budget: u64,
+    error: Option<String>,
+    scan_interval: Interval,
+    interval: Interval,
+}
+
+impl ScriptRunner {
+    pub fn new(scripts_path: PathBuf) -> Self {
+        let host = Rc::new(RefCell::new(ScriptHost {
+            game_data: std::ptr::null_mut(),
+            log: VecDeque::new(),
+        }));
+        let mut engine = Engine::new();
+        engine.set_max_call_levels(32);
+        engine.set_max_string_size(10_000);
+        engine.set_max_array_size(10_000);
+        engine.set_max_map_size(10_000);
+        register_api(&mut engine, &host);
+        let budget = Rc::new(OperationBudget::default());
+        let b = budget.clone();
+        engine.on_progress(move |operations| {
+            b.call.set(operations);
+            (b.used.get() + operations > b.limit.get()).then_some(Dynamic::UNIT)
+        });
+
+        Self {
+            dir: WatchedDir::new(scripts_path),
+            engine,
+            host,
+            scripts: BTreeMap::new(),
+            budget,
+            operation_budget: 100_000,
+            error: None,
+            scan_interval: Interval::new(time::Duration::from_secs(2)),
+            interval: Interval::new(time::Duration::from_millis(300)),
+        }
+    }
+
+    fn scan(&mut self) -> anyhow::Result<()> {
+        let changes = self.dir.scan(|path| has_extension(path, "rhai"))?;
+        for script_path in changes.changed {
+            let enabled = self.scripts.get(&script_path).is_none_or(|script| script.enabled);
+            let ast = fs::read_to_string(&script_path)
+                .map_err(|err| err.to_string())
+                .and_then(|source| self.engine.compile(source).map_err(|err| err.to_string()));
+            let name = script_path.file_stem().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            self.scripts.insert(script_path, Script {
+                name,
+                ast,
+                state: Map::new().into(),
+                initialized: false,
+                enabled,
+                error: None,
+            });
+        }
+        for script_path in changes.removed {
+            self.scripts.remove(&script_path);
+        }
+        Ok(())
+    }
+
+    fn call(engine: &Engine, budget: &OperationBudget, script: &mut Script, function: &str) -> ScriptResult<()> {
+        let Ok(ast) = &script.ast else {
+            return Ok(());
+        };
+        if !ast.iter_functions().any(|f| f.name == function && f.params.is_empty()) {
+            return Ok(());
+        }
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut script.state);
+        let result = engine.call_fn_with_options::<Dynamic>(options, &mut rhai::Scope::new(), ast, function, ());
+        budget.end_call();
+        result?;
+        Ok(())
+    }
+}
+
+impl Plugin for ScriptRunner {
+    fn name(&self) -> String {
+        "scripts".into()
+    }
+
+    fn update(&mut self, game_data: &mut GameData) {
+        if self.scan_interval.check() {
+            self.error = None;
+            if let Err(err) = self.scan() {
+                self.error = Some(format!("Could not read {:?}: {}", self.dir.path(), err));
+            }
+        }
+
+        let _access = GameAccess::new(&self.host, game_data);
+        for script in self.scripts.values_mut() {
+            if !script.enabled || script.ast.is_err() {
+                continue;
+            }
+            self.budget.start_tick(self.operation_budget);
+            let mut result = Ok(());
+            if !script.initialized {
+                script.initialized = true;
+                result = Self::call(&self.engine, &self.budget, script, "init");
+            }
+            if result.is_ok() {
+                result = Self::call(&self.engine, &self.budget, script, "update");
+            }
+            script.error = result.err().map(|err| match *err {
+                EvalAltResult::ErrorTerminated(..) => format!("exceeded the budget of {} operations this tick", self.operation_budget),
+                err => err.to_string(),
+            });
+        }
+    }
+
+    fn update_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Scripts from {:?}", self.dir.path()));
+        ui.add(egui::DragValue::new(&mut self.operation_budget).speed(1000).prefix("Operations per tick: "));
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        for script in self.scripts.values_mut() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut script.enabled, &script.name);
+                if ui.small_button("Restart").clicked() {
+                    script.state = Map::new().into();
+                    script.initialized = false;
+                }
+            });
+            if let Err(err) = &script.ast {
+                ui.colored_label(egui::Color32::RED, err);
+            } else if let Some(err) = &script.error {
+                ui.colored_label(egui::Color32::YELLOW, err);
+            }
+        }
+        ui.collapsing("Output", |ui| {
+            egui::ScrollArea::vertical().max_height(150.).stick_to_bottom(true).show(ui, |ui| {
+                for line in &self.host.borrow().log {
+                    ui.monospace(line);
+                }
+            });
+        });
+    }
+
+    fn update_interval(&mut self) -> &mut Interval {
+        &mut self.interval
+    }
+}
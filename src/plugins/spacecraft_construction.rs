@@ -1,24 +1,253 @@
-use std::{fs, path::PathBuf};
+use std::path::PathBuf;
 
 use super::*;
 
+use structure_loader::StructureLoader;
+use structure_formats::StructureFormat;
+use structure_editor::StructureEditor;
+use build_queue::{BuildQueue, OrderStatus, RecurringOrder};
+use hangar_scheduler::HangarScheduler;
+use deployment::{DeploymentPolicy, DeploymentRule, DeploymentMatch};
+use logging::warn;
+
+const REQUEST_PRIORITY: i32 = 10;
+
+/// Updates a request may go unmatched before it is dropped
+const REQUEST_EXPIRY: u32 = 20;
+
+#[derive(Serialize, Deserialize)]
+struct ConstructionHandoff {
+    auto_deploy: bool,
+    build_queue: BuildQueue,
+    deployment: DeploymentPolicy,
+}
+
 #[derive(Serialize)]
 pub struct SpacecraftConstruction {
-    spacecraft_structures_path: PathBuf,
     update_interval: Interval,
     auto_deploy: bool,
-    spacecraft_structures: Vec<(String, SpacecraftStructure)>,
-    build_queue: Vec<SpacecraftStructure>
+    structure_loader: StructureLoader,
+    build_queue: BuildQueue,
+    new_order_priority: i32,
+    new_order_quantity: usize,
+    new_recurring: RecurringOrder,
+    hangar_status: Vec<String>,
+    scheduler: HangarScheduler,
+    deployment: DeploymentPolicy,
+    new_rule: DeploymentRule,
+    new_rule_waypoint: Vec2,
+    export_name: String,
+    export_format: StructureFormat,
+    export_status: Option<String>,
+    editor: StructureEditor,
+    editor_open: bool,
+    behaviour_names: Vec<String>,
 }
 
 impl SpacecraftConstruction {
     pub fn new(spacecraft_structures_path: PathBuf) -> Self {
         Self {
-            spacecraft_structures_path,
             update_interval: Interval::new(time::Duration::from_millis(500)),
             auto_deploy: true,
-            spacecraft_structures: vec![],
-            build_queue: vec![]
+            structure_loader: StructureLoader::new(spacecraft_structures_path),
+            build_queue: Default::default(),
+            new_order_priority: 0,
+            new_order_quantity: 1,
+            new_recurring: RecurringOrder {
+                structure_name: String::new(),
+                tag: String::new(),
+                minimum_alive: 1,
+                priority: 0,
+                enabled: true,
+                error: None,
+            },
+            hangar_status: vec![],
+            scheduler: HangarScheduler::new(),
+            deployment: Default::default(),
+            new_rule: Default::default(),
+            new_rule_waypoint: Vec2::ZERO,
+            export_name: String::new(),
+            export_format: StructureFormat::Blueprint,
+            export_status: None,
+            editor: StructureEditor::new(),
+            editor_open: false,
+            behaviour_names: vec![],
+        }
+    }
+
+    fn process_orders(&mut self, game_data: &mut GameData) {
+        self.scheduler.update_star_bases(&mut self.build_queue.orders, game_data);
+
+        let ids = self.build_queue.sorted_ids();
+        let orders = ids.iter().filter_map(|id| self.build_queue.orders.iter().find(|order| order.id == *id)).collect::<Vec<_>>();
+        self.scheduler.replan(&orders, game_data);
+
+        for id in ids {
+            let Some(order) = self.build_queue.get_mut(id) else {
+                continue;
+            };
+            let Some(planned) = self.scheduler.first_planned(id) else {
+                order.status = OrderStatus::Failed("no hangar available".into());
+                continue;
+            };
+            // Lower priorities wait until the higher ones can be afforded
+            if !game_data.player().has_materials(&order.structure.materials()) {
+                order.status = OrderStatus::WaitingForMaterials;
+                break;
+            }
+            match game_data.try_execute_cmd(GameCmd::BuildSpacecraft(planned.star_base, order.structure.clone(), planned.hangar)) {
+                Ok(()) => {
+                    order.quantity -= 1;
+                    order.status = OrderStatus::Pending;
+                }
+                Err(err) => order.status = OrderStatus::Failed(err),
+            }
+        }
+        self.build_queue.orders.retain(|order| order.quantity > 0);
+    }
+
+    fn queue_ui(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+        for id in self.build_queue.sorted_ids() {
+            let Some(order) = self.build_queue.get_mut(id) else {
+                continue;
+            };
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut order.priority).prefix("priority: "));
+                ui.label(format!("{} x{}", order.structure_name, order.quantity));
+                match &order.status {
+                    OrderStatus::Pending => ui.label("pending"),
+                    OrderStatus::WaitingForMaterials => ui.colored_label(egui::Color32::YELLOW, "waiting for materials"),
+                    OrderStatus::Failed(err) => ui.colored_label(egui::Color32::RED, format!("failed: {}", err)),
+                };
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(id);
+                }
+            });
+        }
+        if let Some(id) = removed {
+            self.build_queue.remove(id);
+        }
+
+        ui.collapsing("In progress", |ui| {
+            for status in &self.hangar_status {
+                ui.label(status);
+            }
+        });
+    }
+
+    fn deployment_ui(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+        for (index, rule) in self.deployment.rules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut rule.enabled, format!("{:?}", rule.matches));
+                ui.add(egui::DragValue::new(&mut rule.batch_size).clamp_range(1..=50).prefix("batch: "));
+                ui.add(egui::DragValue::new(&mut rule.safe_radius).prefix("safe radius: "));
+                ui.label(format!("start as {:?}", rule.initial_state));
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.deployment.rules.remove(index);
+        }
+
+        ui.separator();
+        let rule = &mut self.new_rule;
+        ui.horizontal(|ui| {
+            if ui.selectable_label(matches!(rule.matches, DeploymentMatch::Tag(_)), "Tag").clicked() {
+                rule.matches = DeploymentMatch::Tag(String::new());
+            }
+            if ui.selectable_label(matches!(rule.matches, DeploymentMatch::Structure(_)), "Structure").clicked() {
+                rule.matches = DeploymentMatch::Structure(String::new());
+            }
+            match &mut rule.matches {
+                DeploymentMatch::Tag(tag) => {
+                    ui.text_edit_singleline(tag);
+                }
+                DeploymentMatch::Structure(name) => {
+                    egui::ComboBox::from_label("Deployed structure")
+                        .selected_text(name.as_str())
+                        .show_ui(ui, |ui| {
+                            for (structure_name, _) in self.structure_loader.structures() {
+                                ui.selectable_value(name, structure_name.clone(), structure_name);
+                            }
+                        });
+                }
+            }
+        });
+        ui.add(egui::DragValue::new(&mut rule.batch_size).clamp_range(1..=50).prefix("batch: "));
+        ui.add(egui::DragValue::new(&mut rule.safe_radius).prefix("safe radius: "));
+        let mut has_initial_state = rule.initial_state.is_some();
+        ui.checkbox(&mut has_initial_state, "initial state");
+        if has_initial_state {
+            let mut state = rule.initial_state.clone().unwrap_or_default();
+            state_combo_box(ui, "Initial state", &mut state, &self.behaviour_names);
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.new_rule_waypoint.x).prefix("x: "));
+                ui.add(egui::DragValue::new(&mut self.new_rule_waypoint.y).prefix("y: "));
+                if ui.button("Waypoint").clicked() {
+                    state = SpacecraftState::MoveTo(self.new_rule_waypoint);
+                }
+            });
+            rule.initial_state = Some(state);
+        } else {
+            rule.initial_state = None;
+        }
+        if ui.button("Add rule").clicked() {
+            self.deployment.rules.push(std::mem::take(&mut self.new_rule));
+        }
+
+        ui.collapsing("Deployed", |ui| {
+            for record in &self.deployment.records {
+                ui.label(format!("{} {:?} from star base {} hangar {}", record.spacecraft, record.tags, record.star_base, record.hangar));
+            }
+        });
+    }
+
+    fn recurring_ui(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+        for (index, recurring) in self.build_queue.recurring.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut recurring.enabled, format!("{} as {}", recurring.structure_name, recurring.tag));
+                ui.add(egui::DragValue::new(&mut recurring.minimum_alive).prefix("min alive: "));
+                ui.add(egui::DragValue::new(&mut recurring.priority).prefix("priority: "));
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+            if let Some(error) = &recurring.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        }
+        if let Some(index) = removed {
+            self.build_queue.recurring.remove(index);
+        }
+
+        ui.separator();
+        egui::ComboBox::from_label("Recurring structure")
+            .selected_text(&self.new_recurring.structure_name)
+            .show_ui(ui, |ui| {
+                for (name, structure) in self.structure_loader.structures() {
+                    if ui.selectable_label(self.new_recurring.structure_name == *name, name).clicked() {
+                        self.new_recurring.structure_name = name.clone();
+                        self.new_recurring.tag = structure.tags.first().cloned().unwrap_or_default();
+                    }
+                }
+            });
+        let tags = self.structure_loader.structure(&self.new_recurring.structure_name).map(|structure| structure.tags.clone()).unwrap_or_default();
+        egui::ComboBox::from_label("Tag")
+            .selected_text(&self.new_recurring.tag)
+            .show_ui(ui, |ui| {
+                for tag in tags {
+                    ui.selectable_value(&mut self.new_recurring.tag, tag.clone(), tag);
+                }
+            });
+        ui.add(egui::DragValue::new(&mut self.new_recurring.minimum_alive).prefix("min alive: "));
+        ui.add(egui::DragValue::new(&mut self.new_recurring.priority).prefix("priority: "));
+        if ui.button("Add recurring order").clicked() && !self.new_recurring.tag.is_empty() {
+            self.build_queue.recurring.push(self.new_recurring.clone());
         }
     }
 }
@@ -32,49 +261,136 @@ impl Plugin for SpacecraftConstruction {
         &mut self.update_interval
     }
 
-    fn update(&mut self, game_data: &mut GameData) {
-        if self.auto_deploy {
-            for (star_base_id, star_base) in game_data.my_star_bases() {
-                for (hangar_index, hangar) in star_base.hangars.into_iter().enumerate() {
-                    if hangar.build_finished() {
-                        game_data.execute_cmd(GameCmd::DeploySpacecraft(star_base_id, hangar_index))
-                    }
-                }
-            } 
-        }
-        self.spacecraft_structures = vec![];
-        fs::create_dir_all(&self.spacecraft_structures_path).unwrap();
-        for dir_entry in self.spacecraft_structures_path.read_dir().unwrap() {
-            let dir_entry = dir_entry.unwrap(); 
-            let structure_path = dir_entry.path();
-            assert!(structure_path.extension().unwrap() == "json");
-            let structure_data_raw = std::fs::read_to_string(structure_path.clone()).unwrap();
-            let structure_data = deserialize_str(&structure_data_raw).unwrap();
-            self.spacecraft_structures.push((structure_path.file_name().unwrap().to_str().unwrap().into(), structure_data));
+    fn save_state(&self) -> Option<(u32, Vec<u8>)> {
+        #[derive(Serialize)]
+        struct ConstructionHandoffRef<'a> {
+            auto_deploy: bool,
+            build_queue: &'a BuildQueue,
+            deployment: &'a DeploymentPolicy,
         }
+        let handoff = ConstructionHandoffRef {
+            auto_deploy: self.auto_deploy,
+            build_queue: &self.build_queue,
+            deployment: &self.deployment,
+        };
+        Some((1, serialize_bytes(&handoff).ok()?))
+    }
 
-        for spacecraft_structure in std::mem::take(&mut self.build_queue) {
-            let mut hangars = game_data.my_star_bases().into_iter().map(|(id, star_base)| star_base.hangars.into_iter().enumerate().map(|(index, hangar)| {
-                let total_build_time = hangar.building_queue.iter().map(|structure| structure.build_time()).sum();
-                (total_build_time-hangar.progress.min(total_build_time), id, index)
-            }).collect::<Vec<_>>()).flatten().collect::<Vec<_>>();
+    fn load_state(&mut self, version: u32, data: &[u8]) -> anyhow::Result<()> {
+        if version != 1 {
+            anyhow::bail!("unknown state version {}", version);
+        }
+        let handoff: ConstructionHandoff = deserialize_bytes(data)?;
+        self.auto_deploy = handoff.auto_deploy;
+        self.build_queue = handoff.build_queue;
+        self.deployment = handoff.deployment;
+        Ok(())
+    }
 
-            hangars.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    fn update(&mut self, game_data: &mut GameData) {
+        self.structure_loader.refresh();
+        self.behaviour_names = game_data.shared.behaviour_names.clone();
+        self.deployment.deploy(game_data, &self.structure_loader, self.auto_deploy);
 
-            if let Some((_, starbase_id, hangar_index)) = hangars.get(0) {
-                game_data.execute_cmd(GameCmd::BuildSpacecraft(*starbase_id, spacecraft_structure, *hangar_index));
-            }
+        for mut request in std::mem::take(&mut game_data.shared.build_requests) {
+            let structure = self.structure_loader.structures().find(|(_, structure)| structure.tags == request.tags);
+            let Some((name, structure)) = structure else {
+                request.unmatched += 1;
+                if request.unmatched < REQUEST_EXPIRY {
+                    game_data.shared.build_requests.push(request);
+                } else {
+                    warn!("No structure with tags {:?}, dropping build request", request.tags);
+                }
+                continue;
+            };
+            self.build_queue.push(name.clone(), structure.clone(), REQUEST_PRIORITY, 1, request.star_base);
         }
+
+        self.build_queue.update_recurring(game_data, &self.structure_loader);
+        game_data.shared.material_needs = self.build_queue.material_needs();
+        self.process_orders(game_data);
+
+        self.hangar_status = game_data.my_star_bases().into_iter().flat_map(|(star_base_id, star_base)| {
+            star_base.hangars.into_iter().enumerate().map(move |(hangar_index, hangar)| {
+                let total_build_time = hangar.building_queue.iter().map(|structure| structure.build_time()).sum::<f32>();
+                let tags = hangar.building_queue.iter().map(|structure| structure.tags.join(" ")).collect::<Vec<_>>();
+                format!("Star base {} hangar {}: {:?} ({:.0}/{:.0})", star_base_id, hangar_index, tags, hangar.progress.min(total_build_time), total_build_time)
+            })
+        }).collect();
     }
 
     fn update_ui(&mut self, ui: &mut egui::Ui) {
         ui.checkbox(&mut self.auto_deploy, "auto deploy");
         
-        ui.label(format!("Directory: {:?}", self.spacecraft_structures_path));
-        for (name, spacecraft_structure) in &self.spacecraft_structures {
-            if ui.button(name).clicked() {
-                self.build_queue.push(spacecraft_structure.clone());
-            }
+        ui.label(format!("Directory: {:?}", self.structure_loader.path()));
+        for (name, spacecraft_structure) in self.structure_loader.structures() {
+            ui.horizontal(|ui| {
+                if ui.button(name).clicked() {
+                    self.build_queue.push(name.clone(), spacecraft_structure.clone(), self.new_order_priority, self.new_order_quantity, None);
+                }
+                if ui.small_button("Edit").clicked() {
+                    self.editor.load(name, spacecraft_structure);
+                    self.editor_open = true;
+                }
+            });
         }
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.new_order_quantity).clamp_range(1..=100).prefix("quantity: "));
+            ui.add(egui::DragValue::new(&mut self.new_order_priority).prefix("priority: "));
+        });
+        self.structure_loader.report_ui(ui);
+
+        ui.collapsing("Build queue", |ui| {
+            self.queue_ui(ui);
+        });
+
+        ui.collapsing("Deployment rules", |ui| {
+            self.deployment_ui(ui);
+        });
+
+        ui.collapsing("Hangar schedule", |ui| {
+            self.scheduler.update_ui(ui);
+        });
+
+        ui.collapsing("Recurring orders", |ui| {
+            self.recurring_ui(ui);
+        });
+
+        ui.checkbox(&mut self.editor_open, "structure editor");
+        egui::Window::new("Structure editor").open(&mut self.editor_open).show(ui.ctx(), |ui| {
+            self.editor.update_ui(ui, self.structure_loader.path());
+        });
+
+        ui.collapsing("Comparison", |ui| {
+            egui::ScrollArea::horizontal().show(ui, |ui| {
+                structure_analysis::comparison_table_ui(ui, &self.structure_loader.analyses());
+            });
+        });
+
+        ui.collapsing("Export", |ui| {
+            egui::ComboBox::from_label("Structure")
+                .selected_text(&self.export_name)
+                .show_ui(ui, |ui| {
+                    for (name, _) in self.structure_loader.structures() {
+                        ui.selectable_value(&mut self.export_name, name.clone(), name);
+                    }
+                });
+            egui::ComboBox::from_label("Format")
+                .selected_text(format!("{:?}", self.export_format))
+                .show_ui(ui, |ui| {
+                    for format in StructureFormat::ALL {
+                        ui.selectable_value(&mut self.export_format, format, format!("{:?}", format));
+                    }
+                });
+            if ui.button("Export").clicked() {
+                self.export_status = Some(match self.structure_loader.export(&self.export_name, self.export_format) {
+                    Ok(path) => format!("Exported to {:?}", path),
+                    Err(err) => format!("Export failed: {}", err),
+                });
+            }
+            if let Some(status) = &self.export_status {
+                ui.label(status);
+            }
+        });
     }
 }
\ No newline at end of file
@@ -1,13 +1,184 @@
 
+use std::{collections::{HashSet, VecDeque}, fs, path::PathBuf};
+
 use super::*;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone, Copy)]
+use behaviour_tree::{BehaviourTree, Blackboard};
+use mining_planner::{MiningPlanner, MINING_RANGE, PROJECTION_HORIZON};
+use triggers::{Trigger, TriggerCondition, TriggerV1};
+use structure_analysis::component_stats;
+use logging::{info, error};
+use watched_dir::{WatchedDir, has_extension};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone)]
 pub enum SpacecraftState {
     #[default]
     Idle,
     Attack,
     Mining,
-    Defense
+    Defense,
+    Retreat,
+    Behaviour(String),
+    AttackTarget(GameObjectId),
+    MoveTo(Vec2)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpacecraftDetails {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub components: Vec<String>,
+    pub target: Option<GameObjectId>,
+    pub last_cmds: Vec<GameCmd>,
+}
+
+impl SpacecraftDetails {
+    pub fn new(spacecraft: &Spacecraft, target: Option<GameObjectId>, last_cmds: Vec<GameCmd>) -> Self {
+        let components = spacecraft.components.iter().map(|(component_id, component)| match component {
+            Component::Engine(engine) => format!("{:?}: engine, active: {}, power: {:.2}", component_id, engine.active, engine.power),
+            Component::Weapon(weapon) => format!("{:?}: weapon, active: {}, rotation: {:.2}", component_id, weapon.active, weapon.rotation),
+            _ => format!("{:?}: structure", component_id),
+        }).collect();
+        Self {
+            position: spacecraft.body.position,
+            velocity: spacecraft.body.velocity,
+            components,
+            target,
+            last_cmds,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpacecraftHealth {
+    components: usize,
+    engines: usize,
+    weapons: usize,
+}
+
+impl SpacecraftHealth {
+    pub fn new(spacecraft: &Spacecraft) -> Self {
+        let placeholders = &spacecraft.structure.component_placeholders;
+        let stats = placeholders.iter().map(|placeholder| component_stats(&placeholder.component_type));
+        Self {
+            components: placeholders.len(),
+            engines: stats.clone().filter(|stats| stats.thrust > 0.).count(),
+            weapons: stats.filter(|stats| stats.weapon).count(),
+        }
+    }
+
+    pub fn losses(&self, spacecraft: &Spacecraft) -> f32 {
+        if self.components == 0 {
+            return 0.;
+        }
+        1. - spacecraft.components.len() as f32 / self.components as f32
+    }
+
+    pub fn should_retreat(&self, spacecraft: &Spacecraft, threshold: f32) -> bool {
+        let (engines, weapons) = count_engines_weapons(spacecraft);
+        let lost_engines = self.engines > 0 && engines == 0;
+        let lost_weapons = self.weapons > 0 && weapons == 0;
+        self.losses(spacecraft) >= threshold || lost_engines || lost_weapons
+    }
+}
+
+pub fn state_combo_box<'a>(ui: &mut egui::Ui, label: &str, state: &mut SpacecraftState, behaviours: impl IntoIterator<Item = &'a String>) {
+    egui::ComboBox::from_label(label)
+        .selected_text(
+            format!("{:?}", state)
+        )
+        .show_ui(ui, |ui| {
+            ui.selectable_value(state, SpacecraftState::Idle, "Idle");
+            ui.selectable_value(state, SpacecraftState::Attack, "Attack");
+            ui.selectable_value(state, SpacecraftState::Mining, "Mining");
+            ui.selectable_value(state, SpacecraftState::Defense, "Defense");
+            ui.selectable_value(state, SpacecraftState::Retreat, "Retreat");
+            for name in behaviours {
+                ui.selectable_value(state, SpacecraftState::Behaviour(name.clone()), format!("Behaviour: {}", name));
+            }
+        });
+}
+
+fn count_engines_weapons(spacecraft: &Spacecraft) -> (usize, usize) {
+    let engines = spacecraft.components.values().filter(|component| matches!(component, Component::Engine(_))).count();
+    let weapons = spacecraft.components.values().filter(|component| matches!(component, Component::Weapon(_))).count();
+    (engines, weapons)
+}
+
+const CONTROL_HANDOFF_VERSION: u32 = 3;
+
+#[derive(Deserialize)]
+struct ControlHandoff {
+    spacecraft_states: HashMap<GameObjectId, SpacecraftState>,
+    selectable_tags: Vec<(String, bool)>,
+    triggers: Vec<Trigger>,
+    auto_retreat: bool,
+    retreat_threshold: f32,
+    queue_replacements: bool,
+    replaced: HashSet<GameObjectId>,
+    retreated: HashSet<GameObjectId>,
+    blackboards: HashMap<GameObjectId, Blackboard>,
+    mining_planner: MiningPlanner,
+}
+
+#[derive(Deserialize)]
+struct ControlHandoffV2 {
+    spacecraft_states: HashMap<GameObjectId, SpacecraftState>,
+    selectable_tags: Vec<(String, bool)>,
+    triggers: Vec<TriggerV1>,
+    auto_retreat: bool,
+    retreat_threshold: f32,
+    queue_replacements: bool,
+    replaced: HashSet<GameObjectId>,
+    blackboards: HashMap<GameObjectId, Blackboard>,
+    mining_planner: MiningPlanner,
+}
+
+impl From<ControlHandoffV2> for ControlHandoff {
+    fn from(handoff: ControlHandoffV2) -> Self {
+        let retreated = handoff.spacecraft_states.iter()
+            .filter(|(_, state)| **state == SpacecraftState::Retreat)
+            .map(|(id, _)| *id)
+            .collect();
+        Self {
+            spacecraft_states: handoff.spacecraft_states,
+            selectable_tags: handoff.selectable_tags,
+            triggers: handoff.triggers.into_iter().map(Trigger::from).collect(),
+            auto_retreat: handoff.auto_retreat,
+            retreat_threshold: handoff.retreat_threshold,
+            queue_replacements: handoff.queue_replacements,
+            replaced: handoff.replaced,
+            retreated,
+            blackboards: handoff.blackboards,
+            mining_planner: handoff.mining_planner,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ControlHandoffV1 {
+    spacecraft_states: HashMap<GameObjectId, SpacecraftState>,
+    selectable_tags: Vec<(String, bool)>,
+    triggers: Vec<TriggerV1>,
+    auto_retreat: bool,
+    retreat_threshold: f32,
+    queue_replacements: bool,
+}
+
+impl From<ControlHandoffV1> for ControlHandoffV2 {
+    fn from(handoff: ControlHandoffV1) -> Self {
+        Self {
+            spacecraft_states: handoff.spacecraft_states,
+            selectable_tags: handoff.selectable_tags,
+            triggers: handoff.triggers,
+            auto_retreat: handoff.auto_retreat,
+            retreat_threshold: handoff.retreat_threshold,
+            queue_replacements: handoff.queue_replacements,
+            replaced: HashSet::new(),
+            blackboards: HashMap::new(),
+            mining_planner: MiningPlanner::new(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -17,18 +188,365 @@ pub struct SpacecraftControl {
     selectable_tags: Vec<(String, bool)>,
     selectable_state: SpacecraftState,
     new_tag_input: String,
-    spacecraft_tags: HashMap<GameObjectId, Vec<String>>
+    spacecraft_tags: HashMap<GameObjectId, Vec<String>>,
+    auto_retreat: bool,
+    retreat_threshold: f32,
+    queue_replacements: bool,
+    replaced: HashSet<GameObjectId>,
+    retreated: HashSet<GameObjectId>,
+    behaviours_dir: WatchedDir,
+    behaviours_interval: Interval,
+    behaviours: BTreeMap<String, BehaviourTree>,
+    #[serde(skip)]
+    behaviour_errors: BTreeMap<PathBuf, String>,
+    blackboards: HashMap<GameObjectId, Blackboard>,
+    triggers: Vec<Trigger>,
+    new_trigger: Trigger,
+    trigger_log: VecDeque<String>,
+    known_materials: Vec<Material>,
+    selected: HashSet<GameObjectId>,
+    spacecraft_details: HashMap<GameObjectId, SpacecraftDetails>,
+    detailed: Option<GameObjectId>,
+    /// Id, description, position and whether the object belongs to an enemy
+    game_objects: Vec<(GameObjectId, String, Vec2, bool)>,
+    waypoint_input: Vec2,
+    mining_planner: MiningPlanner,
 }
 
 impl SpacecraftControl {
-    pub fn new() -> Self {
+    pub fn new(behaviours_path: PathBuf) -> Self {
         Self {
             spacecraft_states: HashMap::new(),
             interval: Interval::new(time::Duration::from_millis(300)),
             selectable_tags: vec![],
             selectable_state: Default::default(),
             new_tag_input: String::new(),
-            spacecraft_tags: Default::default()
+            spacecraft_tags: Default::default(),
+            auto_retreat: true,
+            retreat_threshold: 0.5,
+            queue_replacements: false,
+            replaced: Default::default(),
+            retreated: Default::default(),
+            behaviours_dir: WatchedDir::new(behaviours_path),
+            behaviours_interval: Interval::new(time::Duration::from_secs(2)),
+            behaviours: BTreeMap::new(),
+            behaviour_errors: BTreeMap::new(),
+            blackboards: Default::default(),
+            triggers: vec![],
+            new_trigger: Default::default(),
+            trigger_log: VecDeque::new(),
+            known_materials: vec![],
+            selected: Default::default(),
+            spacecraft_details: Default::default(),
+            detailed: None,
+            game_objects: vec![],
+            waypoint_input: Vec2::ZERO,
+            mining_planner: MiningPlanner::new(),
+        }
+    }
+
+    fn control_spacecraft(
+        &mut self,
+        (id, spacecraft): (&GameObjectId, &Spacecraft),
+        spacecraft_state: SpacecraftState,
+        game_data: &mut GameData,
+    ) -> Option<GameObjectId> {
+        let mut current_target = None;
+        match spacecraft_state {
+            SpacecraftState::Idle => {
+                if let Some(target) = game_data.closest_enemy_target(&spacecraft.body.position) {
+                    current_target = Some(target.0);
+                    game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1));
+                }
+            }
+            SpacecraftState::Attack => {
+                if let Some(target) = game_data.closest_enemy_target(&spacecraft.body.position) {
+                    current_target = Some(target.0);
+                    game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1));
+                }
+                if let Some(enemy_starbase) = game_data.closest_enemy_star_base(&spacecraft.body.position) {
+                    game_data.execute_cmds(improved_fly_to((id, spacecraft), enemy_starbase.1.body));
+                }
+            }
+            SpacecraftState::Mining => {
+                let assigned_asteroid = self.mining_planner.assignment(id)
+                    .and_then(|asteroid_id| Some((asteroid_id, game_data.game_object_body(asteroid_id)?)));
+
+                let mut has_taken_shot = false;
+                if let Some((asteroid_id, asteroid_body)) = assigned_asteroid {
+                    game_data.execute_cmds(improved_fly_to((id, spacecraft), asteroid_body.clone()));
+                    if asteroid_body.position.distance(spacecraft.body.position) < MINING_RANGE {
+                        current_target = Some(asteroid_id);
+                        game_data.execute_cmds(predictive_shoot_at((id, spacecraft), asteroid_body));
+                        has_taken_shot = true;
+                    }
+                }
+
+                if !has_taken_shot && let Some(target) = game_data.closest_enemy_target(&spacecraft.body.position) {
+                    current_target = Some(target.0);
+                    game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1));
+                }
+
+            }
+            SpacecraftState::Defense => {
+                if let Some(target) = game_data.closest_enemy_target(&spacecraft.body.position) {
+                    current_target = Some(target.0);
+                    game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1));
+                }
+
+                if let Some(star_base) = game_data.closest_my_star_base(&spacecraft.body.position) {
+                    game_data.execute_cmds(improved_fly_to((id, spacecraft), star_base.1.body));
+                }
+            }
+            SpacecraftState::Retreat => {
+                if let Some(target) = game_data.closest_enemy_spacecraft(&spacecraft.body.position) {
+                    current_target = Some(target.0);
+                    game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1.body));
+                }
+
+                let Some((star_base_id, star_base)) = game_data.closest_my_star_base(&spacecraft.body.position) else {
+                    return current_target;
+                };
+                game_data.execute_cmds(improved_fly_to((id, spacecraft), star_base.body));
+
+                if self.queue_replacements && self.replaced.insert(*id) {
+                    game_data.request_build(BuildRequest::new(spacecraft.tags.clone(), Some(star_base_id)));
+                }
+            }
+            SpacecraftState::Behaviour(name) => {
+                let behaviour = self.behaviours.get(&name)?;
+                let blackboard = self.blackboards.entry(*id).or_default();
+                behaviour.tick((id, spacecraft), game_data, blackboard);
+                current_target = blackboard.target;
+            }
+            SpacecraftState::AttackTarget(target_id) => {
+                let Some(target) = game_data.game_object_body(target_id) else {
+                    self.spacecraft_states.insert(*id, SpacecraftState::Idle);
+                    return None;
+                };
+                current_target = Some(target_id);
+                game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.clone()));
+                game_data.execute_cmds(improved_fly_to((id, spacecraft), target));
+            }
+            SpacecraftState::MoveTo(waypoint) => {
+                if let Some(target) = game_data.closest_enemy_target(&spacecraft.body.position) {
+                    current_target = Some(target.0);
+                    game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1));
+                }
+
+                let mut target = spacecraft.body.clone();
+                target.position = waypoint;
+                target.velocity = Vec2::ZERO;
+                game_data.execute_cmds(improved_fly_to((id, spacecraft), target));
+            }
+        }
+        current_target
+    }
+
+    fn evaluate_triggers(&mut self, game_data: &GameData) {
+        for trigger in &mut self.triggers {
+            let (fired, ids) = trigger.evaluate(game_data);
+            for id in ids {
+                self.spacecraft_states.insert(id, trigger.state.clone());
+            }
+            if fired {
+                let description = trigger.description();
+                info!("Trigger fired: {}", description);
+                self.trigger_log.push_front(description);
+                self.trigger_log.truncate(50);
+            }
+        }
+    }
+
+    fn spacecrafts_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Select all").clicked() {
+                self.selected = self.spacecraft_tags.keys().copied().collect();
+            }
+            if ui.button("Clear selection").clicked() {
+                self.selected.clear();
+            }
+        });
+
+        let mut ids = self.spacecraft_tags.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            let state = self.spacecraft_states.get(&id).cloned().unwrap_or_default();
+            ui.horizontal(|ui| {
+                let mut selected = self.selected.contains(&id);
+                if ui.checkbox(&mut selected, format!("{}: {:?}", id, state)).changed() {
+                    if selected {
+                        self.selected.insert(id);
+                    } else {
+                        self.selected.remove(&id);
+                    }
+                }
+                if ui.selectable_label(self.detailed == Some(id), "Details").clicked() {
+                    self.detailed = if self.detailed == Some(id) { None } else { Some(id) };
+                }
+            });
+        }
+
+        if let Some(id) = self.detailed {
+            ui.separator();
+            self.spacecraft_detail_ui(ui, id);
+        }
+    }
+
+    fn spacecraft_detail_ui(&mut self, ui: &mut egui::Ui, id: GameObjectId) {
+        let Some(details) = self.spacecraft_details.get(&id) else {
+            ui.label(format!("Spacecraft {} is gone", id));
+            return;
+        };
+        ui.label(format!("Spacecraft {}", id));
+        ui.label(format!("Tags: {:?}", self.spacecraft_tags.get(&id).cloned().unwrap_or_default()));
+        ui.label(format!("Position: {:.1}, {:.1}", details.position.x, details.position.y));
+        ui.label(format!("Velocity: {:.1}, {:.1} ({:.1})", details.velocity.x, details.velocity.y, details.velocity.length()));
+        let target = details.target.and_then(|target| self.game_objects.iter().find(|x| x.0 == target));
+        ui.label(format!("Target: {}", target.map(|x| x.1.as_str()).unwrap_or("none")));
+        ui.collapsing(format!("Components ({})", details.components.len()), |ui| {
+            for component in &details.components {
+                ui.label(component);
+            }
+        });
+        ui.collapsing(format!("Last commands ({})", details.last_cmds.len()), |ui| {
+            for cmd in &details.last_cmds {
+                ui.label(format!("{:?}", cmd));
+            }
+        });
+
+        let current = self.spacecraft_states.get(&id).cloned().unwrap_or_default();
+        let mut state = current.clone();
+        state_combo_box(ui, "Spacecraft state", &mut state, self.behaviours.keys());
+
+        egui::ComboBox::from_label("Pin to")
+            .selected_text("choose game object")
+            .show_ui(ui, |ui| {
+                for (object_id, description, position, enemy) in self.game_objects.iter().filter(|x| x.0 != id) {
+                    ui.horizontal(|ui| {
+                        ui.label(description);
+                        if *enemy && ui.small_button("Attack").clicked() {
+                            state = SpacecraftState::AttackTarget(*object_id);
+                        }
+                        if ui.small_button("Move").clicked() {
+                            state = SpacecraftState::MoveTo(*position);
+                        }
+                    });
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.waypoint_input.x).prefix("x: "));
+            ui.add(egui::DragValue::new(&mut self.waypoint_input.y).prefix("y: "));
+            if ui.button("Move to waypoint").clicked() {
+                state = SpacecraftState::MoveTo(self.waypoint_input);
+            }
+        });
+
+        if state != current {
+            self.spacecraft_states.insert(id, state);
+        }
+    }
+
+    fn triggers_ui(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+        for (index, trigger) in self.triggers.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut trigger.enabled, trigger.description());
+                if ui.button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.triggers.remove(index);
+        }
+
+        ui.separator();
+        let condition = &mut self.new_trigger.condition;
+        let default_material = self.known_materials.first().cloned();
+        egui::ComboBox::from_label("Condition")
+            .selected_text(
+                format!("{:?}", condition)
+            )
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(matches!(condition, TriggerCondition::EnemyNearMyStarBase(_)), "EnemyNearMyStarBase").clicked() {
+                    *condition = TriggerCondition::EnemyNearMyStarBase(1000.);
+                }
+                if let Some(material) = default_material {
+                    if ui.selectable_label(matches!(condition, TriggerCondition::MaterialAbove(..)), "MaterialAbove").clicked() {
+                        *condition = TriggerCondition::MaterialAbove(material.clone(), 1000.);
+                    }
+                    if ui.selectable_label(matches!(condition, TriggerCondition::MaterialBelow(..)), "MaterialBelow").clicked() {
+                        *condition = TriggerCondition::MaterialBelow(material, 100.);
+                    }
+                }
+                if ui.selectable_label(matches!(condition, TriggerCondition::TagCountBelow(..)), "TagCountBelow").clicked() {
+                    *condition = TriggerCondition::TagCountBelow(String::new(), 1);
+                }
+            });
+        match condition {
+            TriggerCondition::EnemyNearMyStarBase(distance) => {
+                ui.add(egui::DragValue::new(distance).prefix("distance: "));
+            }
+            TriggerCondition::MaterialAbove(material, amount) | TriggerCondition::MaterialBelow(material, amount) => {
+                egui::ComboBox::from_label("Material")
+                    .selected_text(format!("{:?}", material))
+                    .show_ui(ui, |ui| {
+                        for known_material in &self.known_materials {
+                            ui.selectable_value(material, known_material.clone(), format!("{:?}", known_material));
+                        }
+                    });
+                ui.add(egui::DragValue::new(amount).prefix("amount: "));
+            }
+            TriggerCondition::TagCountBelow(tag, count) => {
+                ui.text_edit_singleline(tag);
+                ui.add(egui::DragValue::new(count).prefix("count: "));
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.label("Tag");
+            ui.text_edit_singleline(&mut self.new_trigger.tag);
+        });
+        state_combo_box(ui, "Set state", &mut self.new_trigger.state, self.behaviours.keys());
+        if ui.button("Add trigger").clicked() {
+            self.triggers.push(std::mem::take(&mut self.new_trigger));
+        }
+
+        ui.collapsing("Fired triggers", |ui| {
+            for entry in &self.trigger_log {
+                ui.label(entry);
+            }
+        });
+    }
+
+    fn load_behaviours(&mut self) {
+        let changes = match self.behaviours_dir.scan(|path| has_extension(path, "json")) {
+            Ok(changes) => changes,
+            Err(err) => {
+                error!("Could not read {:?}: {}", self.behaviours_dir.path(), err);
+                return;
+            }
+        };
+        let name = |path: &PathBuf| path.file_stem().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        for behaviour_path in changes.changed {
+            let behaviour = fs::read_to_string(&behaviour_path)
+                .map_err(anyhow::Error::from)
+                .and_then(|raw| deserialize_str::<BehaviourTree>(&raw).map_err(|err| anyhow::anyhow!("{:?}", err)));
+            match behaviour {
+                Ok(behaviour) => {
+                    self.behaviours.insert(name(&behaviour_path), behaviour);
+                    self.behaviour_errors.remove(&behaviour_path);
+                }
+                Err(err) => {
+                    error!("Error loading behaviour {:?}: {}", behaviour_path, err);
+                    self.behaviour_errors.insert(behaviour_path, err.to_string());
+                }
+            }
+        }
+        for behaviour_path in changes.removed {
+            self.behaviours.remove(&name(&behaviour_path));
+            self.behaviour_errors.remove(&behaviour_path);
         }
     }
 }
@@ -49,36 +567,52 @@ impl Plugin for SpacecraftControl {
             ui.checkbox(selected, tag_name.clone());
         }
 
-        egui::ComboBox::from_label("State")
-            .selected_text(
-                format!("{:?}", self.selectable_state)
-            )
-            .show_ui(ui, |ui| {
-                ui.selectable_value(&mut self.selectable_state, SpacecraftState::Idle, "Idle");
-                ui.selectable_value(&mut self.selectable_state, SpacecraftState::Attack, "Attack");
-                ui.selectable_value(&mut self.selectable_state, SpacecraftState::Mining, "Mining");
-                ui.selectable_value(&mut self.selectable_state, SpacecraftState::Defense, "Defense");
-            });
+        state_combo_box(ui, "State", &mut self.selectable_state, self.behaviours.keys());
+        for (path, error) in &self.behaviour_errors {
+            ui.colored_label(egui::Color32::RED, format!("{:?}: {}", path.file_name().unwrap_or_default(), error));
+        }
+
+        if ui.button("Apply to selected").clicked() {
+            for id in &self.selected {
+                self.spacecraft_states.insert(*id, self.selectable_state.clone());
+            }
+        }
 
         if ui.button("Apply").clicked() {
             let active_tags = self.selectable_tags.clone().into_iter().filter_map(|x| if x.1 {Some(x.0)} else {None}).collect::<Vec<_>>();
             for (id, tags) in &self.spacecraft_tags {
                 for active_tag in &active_tags {
                     if tags.contains(active_tag) {
-                        *self.spacecraft_states.entry(*id).or_default() = self.selectable_state;
+                        *self.spacecraft_states.entry(*id).or_default() = self.selectable_state.clone();
                     }
                 }
             }
         }
 
-        ui.collapsing("Spacecraft states", |ui| {
-            for (id, spacecraft_state) in &self.spacecraft_states {
-                ui.label(format!("{}: {:?}", id, spacecraft_state));
-            }
+        ui.separator();
+        ui.checkbox(&mut self.auto_retreat, "auto retreat when damaged");
+        ui.add(egui::Slider::new(&mut self.retreat_threshold, 0.0..=1.0).text("retreat at losses"));
+        ui.checkbox(&mut self.queue_replacements, "queue replacements for retreating ships");
+
+        ui.collapsing("Mining planner", |ui| {
+            self.mining_planner.update_ui(ui, &self.known_materials);
+        });
+
+        ui.collapsing("Triggers", |ui| {
+            self.triggers_ui(ui);
+        });
+
+        ui.collapsing("Spacecrafts", |ui| {
+            self.spacecrafts_ui(ui);
         });
     }
     
     fn update(&mut self, game_data: &mut GameData) {
+        if self.behaviours_interval.check() {
+            self.load_behaviours();
+        }
+        game_data.shared.behaviour_names = self.behaviours.keys().cloned().collect();
+
         let spacecrafts = game_data.my_spacecrafts();
 
         self.spacecraft_states.retain(|id, _| {
@@ -89,57 +623,128 @@ impl Plugin for SpacecraftControl {
             spacecrafts.contains_key(id)
         });
 
+        self.replaced.retain(|id| {
+            spacecrafts.contains_key(id)
+        });
+
+        self.retreated.retain(|id| {
+            spacecrafts.contains_key(id)
+        });
+
+        self.blackboards.retain(|id, _| {
+            spacecrafts.contains_key(id)
+        });
+
+        self.selected.retain(|id| {
+            spacecrafts.contains_key(id)
+        });
+
+        self.spacecraft_details.retain(|id, _| {
+            spacecrafts.contains_key(id)
+        });
+
+        self.game_objects = game_data.game_objects.iter().filter_map(|(id, game_object)| {
+            let (description, position, enemy) = match game_object {
+                GameObject::Spacecraft(spacecraft) if spacecraft.owner == game_data.player_id => (format!("My spacecraft {}", id), spacecraft.body.position, false),
+                GameObject::Spacecraft(spacecraft) => (format!("Enemy spacecraft {}", id), spacecraft.body.position, true),
+                GameObject::StarBase(star_base) if star_base.owner == game_data.player_id => (format!("My star base {}", id), star_base.body.position, false),
+                GameObject::StarBase(star_base) => (format!("Enemy star base {}", id), star_base.body.position, true),
+                GameObject::Asteroid(asteroid) => (format!("Asteroid {} ({:?})", id, asteroid.material), asteroid.body.position, false),
+                _ => return None,
+            };
+            Some((*id, description, position, enemy))
+        }).collect();
+
         for (id, spacecraft) in &spacecrafts {
-            let spacecraft_state = *self.spacecraft_states.entry(*id).or_default();
             self.spacecraft_tags.insert(*id, spacecraft.tags.clone());
-            match spacecraft_state {
-                SpacecraftState::Idle => {
-                    if let Some(target) = game_data.closest_enemy_target(&spacecraft.body.position) {
-                        game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1));
-                    }
-                }
-                SpacecraftState::Attack => {
-                    if let Some(target) = game_data.closest_enemy_target(&spacecraft.body.position) {
-                        game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1));
-                    }
-                    if let Some(enemy_starbase) = game_data.closest_enemy_star_base(&spacecraft.body.position) {
-                        game_data.execute_cmds(improved_fly_to((id, spacecraft), enemy_starbase.1.body));
-                    }
-                }
-                SpacecraftState::Mining => {
-                    let least_material = *game_data.player().materials.iter().min_by(|&a, &b| a.1.partial_cmp(b.1).unwrap()).unwrap().0;
-                    let closest_asteroid_with_least_material = game_data.asteroids().into_iter().filter(|asteroid| asteroid.material == least_material).min_by(|&a, &b| a.body.position.distance(spacecraft.body.position).partial_cmp(&b.body.position.distance(spacecraft.body.position)).unwrap());
+        }
+        self.known_materials = game_data.player().materials.keys().cloned().collect();
+        let mut assigned = HashSet::new();
+        for (id, state) in std::mem::take(&mut game_data.shared.state_assignments) {
+            if spacecrafts.contains_key(&id) {
+                self.spacecraft_states.insert(id, state);
+                assigned.insert(id);
+            }
+        }
+        self.evaluate_triggers(game_data);
 
-                    let mut has_taken_shot = false;
-                    if let Some(asteroid) = closest_asteroid_with_least_material.cloned() {
-                        game_data.execute_cmds(improved_fly_to((id, spacecraft), asteroid.body.clone()));
-                        if asteroid.body.position.distance(spacecraft.body.position) < 200.0 {
-                            game_data.execute_cmds(predictive_shoot_at((id, spacecraft), asteroid.body));
-                            has_taken_shot = true;
-                        }
-                    }
+        let mut miners = spacecrafts.clone();
+        miners.retain(|id, _| self.spacecraft_states.get(id) == Some(&SpacecraftState::Mining));
+        self.mining_planner.plan(&miners, game_data);
+        game_data.shared.projected_income = game_data.player().materials.keys()
+            .map(|material| (material.clone(), self.mining_planner.projected_income(material, PROJECTION_HORIZON)))
+            .collect();
 
-                    if !has_taken_shot && let Some(target) = game_data.closest_enemy_target(&spacecraft.body.position) {
-                        game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1));
-                    }
-
-                }
-                SpacecraftState::Defense => {
-                    if let Some(target) = game_data.closest_enemy_target(&spacecraft.body.position) {
-                        game_data.execute_cmds(predictive_shoot_at((id, spacecraft), target.1));
-                    }
+        for (id, spacecraft) in &spacecrafts {
+            let health = SpacecraftHealth::new(spacecraft);
+            if !health.should_retreat(spacecraft, self.retreat_threshold) {
+                self.retreated.remove(id);
+            } else if self.auto_retreat && self.retreated.insert(*id) && !assigned.contains(id) {
+                self.spacecraft_states.insert(*id, SpacecraftState::Retreat);
+            }
 
-                    if let Some(star_base) = game_data.closest_my_star_base(&spacecraft.body.position) {
-                        game_data.execute_cmds(improved_fly_to((id, spacecraft), star_base.1.body));
-                    }
-                }
+            let spacecraft_state = self.spacecraft_states.entry(*id).or_default().clone();
+            let cmds_before = game_data.network_game_cmds.len();
+            let target = self.control_spacecraft((id, spacecraft), spacecraft_state, game_data);
+            if let Some(body) = target.and_then(|target| game_data.game_object_body(target)) {
+                debug_draw::draw(debug_draw::Layer::Targets, id, vec![debug_draw::Shape::Line(spacecraft.body.position, body.position)]);
             }
+            self.spacecraft_details.insert(*id, SpacecraftDetails::new(spacecraft, target, game_data.network_game_cmds[cmds_before..].to_vec()));
         }
 
     }
     fn update_interval(&mut self) -> &mut Interval {
         &mut self.interval
     }
+
+    fn save_state(&self) -> Option<(u32, Vec<u8>)> {
+        #[derive(Serialize)]
+        struct ControlHandoffRef<'a> {
+            spacecraft_states: &'a HashMap<GameObjectId, SpacecraftState>,
+            selectable_tags: &'a Vec<(String, bool)>,
+            triggers: &'a Vec<Trigger>,
+            auto_retreat: bool,
+            retreat_threshold: f32,
+            queue_replacements: bool,
+            replaced: &'a HashSet<GameObjectId>,
+            retreated: &'a HashSet<GameObjectId>,
+            blackboards: &'a HashMap<GameObjectId, Blackboard>,
+            mining_planner: &'a MiningPlanner,
+        }
+        let handoff = ControlHandoffRef {
+            spacecraft_states: &self.spacecraft_states,
+            selectable_tags: &self.selectable_tags,
+            triggers: &self.triggers,
+            auto_retreat: self.auto_retreat,
+            retreat_threshold: self.retreat_threshold,
+            queue_replacements: self.queue_replacements,
+            replaced: &self.replaced,
+            retreated: &self.retreated,
+            blackboards: &self.blackboards,
+            mining_planner: &self.mining_planner,
+        };
+        Some((CONTROL_HANDOFF_VERSION, serialize_bytes(&handoff).ok()?))
+    }
+
+    fn load_state(&mut self, version: u32, data: &[u8]) -> anyhow::Result<()> {
+        let handoff: ControlHandoff = match version {
+            1 => ControlHandoffV2::from(deserialize_bytes::<ControlHandoffV1>(data)?).into(),
+            2 => deserialize_bytes::<ControlHandoffV2>(data)?.into(),
+            CONTROL_HANDOFF_VERSION => deserialize_bytes(data)?,
+            _ => anyhow::bail!("unknown state version {}", version),
+        };
+        self.spacecraft_states = handoff.spacecraft_states;
+        self.selectable_tags = handoff.selectable_tags;
+        self.triggers = handoff.triggers;
+        self.auto_retreat = handoff.auto_retreat;
+        self.retreat_threshold = handoff.retreat_threshold;
+        self.queue_replacements = handoff.queue_replacements;
+        self.replaced = handoff.replaced;
+        self.retreated = handoff.retreated;
+        self.blackboards = handoff.blackboards;
+        self.mining_planner = handoff.mining_planner;
+        Ok(())
+    }
 }
 
 
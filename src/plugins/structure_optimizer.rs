@@ -0,0 +1,316 @@
+use std::{path::PathBuf, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}}, thread};
+
+use super::*;
+
+use simulation::{Scenario, Simulation, Snapshot};
+use structure_editor::COMPONENT_TYPES;
+use structure_loader::StructureLoader;
+use logging::error;
+
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let mut seed = [0; 8];
+        getrandom::getrandom(&mut seed).ok();
+        Self(u64::from_le_bytes(seed) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+const MAX_ATTEMPTS_PER_CANDIDATE: usize = 100;
+
+const ORIENTATIONS: [Orientation; 4] = [Orientation::Up, Orientation::Right, Orientation::Down, Orientation::Left];
+
+fn mutate(structure: &SpacecraftStructure, rng: &mut Rng) -> SpacecraftStructure {
+    let mut result = structure.clone();
+    let placeholders = &mut result.component_placeholders;
+    let index = rng.below(placeholders.len());
+    match rng.below(4) {
+        // Add a component next to an existing one
+        0 => {
+            let neighbour = placeholders[index].position;
+            let offset = [ivec2(1, 0), ivec2(-1, 0), ivec2(0, 1), ivec2(0, -1)][rng.below(4)];
+            let component_type = COMPONENT_TYPES[1 + rng.below(COMPONENT_TYPES.len() - 1)].clone();
+            placeholders.push(ComponentPlaceholder::new(component_type, neighbour + offset, ORIENTATIONS[rng.below(4)].clone()));
+        }
+        1 if !matches!(placeholders[index].component_type, ComponentType::Central) => {
+            placeholders.remove(index);
+        }
+        2 => {
+            placeholders[index].orientation = ORIENTATIONS[rng.below(4)].clone();
+        }
+        _ if !matches!(placeholders[index].component_type, ComponentType::Central) => {
+            placeholders[index].position += [ivec2(1, 0), ivec2(-1, 0), ivec2(0, 1), ivec2(0, -1)][rng.below(4)];
+        }
+        _ => {}
+    }
+    result
+}
+
+fn within_budget(structure: &SpacecraftStructure, budget: &HashMap<Material, f32>) -> bool {
+    structure.materials().into_iter().all(|(material, amount)| amount <= budget.get(&material).copied().unwrap_or(0.))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizerSettings {
+    pub scenario: Scenario,
+    pub population: usize,
+    pub generations: usize,
+    pub duration: f32,
+    /// Budget per material as a multiple of the seed structure's cost
+    pub budget_factor: f32,
+    pub keep_best: usize,
+}
+
+#[derive(Debug, Default)]
+struct OptimizerProgress {
+    generation: usize,
+    best: Vec<(f32, SpacecraftStructure)>,
+    log: Vec<String>,
+    error: Option<String>,
+    done: bool,
+}
+
+fn optimize(simulation_source: Snapshot, player_id: PlayerId, seed: SpacecraftStructure, settings: OptimizerSettings, progress: Arc<Mutex<OptimizerProgress>>, cancel: Arc<AtomicBool>) {
+    let mut rng = Rng::new();
+    let budget = seed.materials().into_iter().map(|(material, amount)| (material, amount * settings.budget_factor)).collect::<HashMap<_, _>>();
+    let evaluate = |structure: &SpacecraftStructure| -> Option<f32> {
+        let simulation = Simulation::new(&simulation_source, player_id).ok()?;
+        simulation.evaluate(structure, settings.scenario, settings.duration).ok()
+    };
+
+    let mut population = vec![];
+    if let Some(score) = evaluate(&seed) {
+        population.push((score, seed.clone()));
+    }
+
+    for generation in 0..settings.generations {
+        let mut offspring = vec![];
+        let mut attempts = 0;
+        while offspring.len() < settings.population {
+            if cancel.load(Ordering::Relaxed) {
+                progress.lock().unwrap().done = true;
+                return;
+            }
+            attempts += 1;
+            if attempts > settings.population * MAX_ATTEMPTS_PER_CANDIDATE {
+                let mut progress = progress.lock().unwrap();
+                progress.error = Some(format!("Generation {} found only {} of {} valid candidates within budget, try a larger budget", generation + 1, offspring.len(), settings.population));
+                progress.done = true;
+                return;
+            }
+            let parent = population.get(rng.below(population.len())).map(|x| &x.1).unwrap_or(&seed);
+            let child = mutate(parent, &mut rng);
+            if !child.valid() || !within_budget(&child, &budget) {
+                continue;
+            }
+            if let Some(score) = evaluate(&child) {
+                offspring.push((score, child));
+            }
+        }
+
+        population.extend(offspring);
+        population.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        population.truncate(settings.population.max(1));
+
+        let mut progress = progress.lock().unwrap();
+        progress.generation = generation + 1;
+        progress.best = population.iter().take(settings.keep_best).cloned().collect();
+        progress.log.push(format!("Generation {}: best score {:.2}", generation + 1, population.first().map(|x| x.0).unwrap_or(0.)));
+    }
+    progress.lock().unwrap().done = true;
+}
+
+#[derive(Serialize, Deserialize)]
+struct OptimizerHandoff {
+    seed_name: String,
+    settings: OptimizerSettings,
+}
+
+pub struct StructureOptimizer {
+    update_interval: Interval,
+    structure_loader: StructureLoader,
+    seed_name: String,
+    settings: OptimizerSettings,
+    start_requested: bool,
+    running: bool,
+    progress: Arc<Mutex<OptimizerProgress>>,
+    cancel: Arc<AtomicBool>,
+    worker: Option<thread::JoinHandle<()>>,
+    status: Option<String>,
+}
+
+impl StructureOptimizer {
+    pub fn new(spacecraft_structures_path: PathBuf) -> Self {
+        Self {
+            update_interval: Interval::new(time::Duration::from_millis(500)),
+            structure_loader: StructureLoader::new(spacecraft_structures_path),
+            seed_name: String::new(),
+            settings: OptimizerSettings {
+                scenario: Scenario::Combat,
+                population: 8,
+                generations: 10,
+                duration: 60.,
+                budget_factor: 1.,
+                keep_best: 3,
+            },
+            start_requested: false,
+            running: false,
+            progress: Default::default(),
+            cancel: Default::default(),
+            worker: None,
+            status: None,
+        }
+    }
+
+    fn stop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        if let Some(worker) = self.worker.take() {
+            worker.join().ok();
+        }
+    }
+
+    fn save_best(&mut self) {
+        let progress = self.progress.lock().unwrap();
+        let scenario = format!("{:?}", self.settings.scenario).to_lowercase();
+        let mut saved = vec![];
+        for (rank, (score, structure)) in progress.best.iter().enumerate() {
+            let path = self.structure_loader.path().join(format!("optimized_{}_{}_score_{:.0}.json", scenario, rank + 1, score));
+            match serde_json::to_string(structure).map_err(anyhow::Error::from).and_then(|raw| Ok(std::fs::write(&path, raw)?)) {
+                Ok(()) => saved.push(format!("{:?}", path)),
+                Err(err) => error!("Error saving optimized structure: {}", err),
+            }
+        }
+        self.status = Some(format!("Saved {}", saved.join(", ")));
+    }
+}
+
+impl Plugin for StructureOptimizer {
+    fn name(&self) -> String {
+        "structure optimizer".into()
+    }
+
+    fn update_interval(&mut self) -> &mut Interval {
+        &mut self.update_interval
+    }
+
+    fn update(&mut self, game_data: &mut GameData) {
+        self.structure_loader.refresh();
+
+        if self.running && self.progress.lock().unwrap().done {
+            self.running = false;
+            if let Some(worker) = self.worker.take() {
+                worker.join().ok();
+            }
+            if let Some(error) = self.progress.lock().unwrap().error.clone() {
+                error!("Optimization failed: {}", error);
+                self.status = Some(error);
+            } else {
+                self.save_best();
+            }
+        }
+
+        if !std::mem::take(&mut self.start_requested) || self.running {
+            return;
+        }
+        let Some(seed) = self.structure_loader.structure(&self.seed_name).cloned() else {
+            self.status = Some("Choose a seed structure first".into());
+            return;
+        };
+        let simulation_source = match Snapshot::new(game_data.game) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                self.status = Some(format!("Could not copy the game: {:?}", err));
+                return;
+            }
+        };
+
+        self.progress = Default::default();
+        self.cancel = Default::default();
+        self.running = true;
+        self.status = None;
+        let (player_id, settings, progress, cancel) = (game_data.player_id, self.settings.clone(), self.progress.clone(), self.cancel.clone());
+        self.worker = Some(thread::spawn(move || optimize(simulation_source, player_id, seed, settings, progress, cancel)));
+    }
+
+    fn unload(&mut self) {
+        // The thread runs code of this build, so it has to finish before the library is unloaded
+        self.stop();
+    }
+
+    fn save_state(&self) -> Option<(u32, Vec<u8>)> {
+        let handoff = OptimizerHandoff {
+            seed_name: self.seed_name.clone(),
+            settings: self.settings.clone(),
+        };
+        Some((1, serialize_bytes(&handoff).ok()?))
+    }
+
+    fn load_state(&mut self, version: u32, data: &[u8]) -> anyhow::Result<()> {
+        if version != 1 {
+            anyhow::bail!("unknown state version {}", version);
+        }
+        let handoff: OptimizerHandoff = deserialize_bytes(data)?;
+        self.seed_name = handoff.seed_name;
+        self.settings = handoff.settings;
+        Ok(())
+    }
+
+    fn update_ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Seed structure")
+            .selected_text(&self.seed_name)
+            .show_ui(ui, |ui| {
+                for (name, _) in self.structure_loader.structures() {
+                    ui.selectable_value(&mut self.seed_name, name.clone(), name);
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.settings.scenario, Scenario::Combat, "Combat");
+            ui.selectable_value(&mut self.settings.scenario, Scenario::Mining, "Mining");
+        });
+        ui.add(egui::Slider::new(&mut self.settings.population, 2..=32).text("population"));
+        ui.add(egui::Slider::new(&mut self.settings.generations, 1..=100).text("generations"));
+        ui.add(egui::Slider::new(&mut self.settings.duration, 10.0..=300.0).text("simulated seconds"));
+        ui.add(egui::Slider::new(&mut self.settings.budget_factor, 0.5..=3.0).text("material budget (x seed)"));
+        ui.add(egui::Slider::new(&mut self.settings.keep_best, 1..=10).text("designs to save"));
+
+        if self.running {
+            if ui.button("Cancel").clicked() {
+                self.cancel.store(true, Ordering::Relaxed);
+            }
+        } else if ui.button("Start").clicked() {
+            self.start_requested = true;
+        }
+
+        let progress = self.progress.lock().unwrap();
+        ui.label(format!("Generation {}/{}", progress.generation, self.settings.generations));
+        for (score, structure) in &progress.best {
+            ui.label(format!("{:.2}: {} components", score, structure.component_placeholders.len()));
+        }
+        ui.collapsing("Log", |ui| {
+            for entry in &progress.log {
+                ui.label(entry);
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
+
+impl Drop for StructureOptimizer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::{BTreeSet, HashSet, VecDeque}, fmt::Write as _, path::PathBuf, time::Instant};
+
+use egui::plot::{Legend, Line, Plot, PlotPoints};
+
+use super::*;
+
+use mining_planner::MinedTracker;
+
+/// Stock is polled this often between samples so mining is not hidden by spending in the same sample
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+struct Sample {
+    time: f64,
+    values: BTreeMap<String, f64>,
+}
+
+pub struct Telemetry {
+    export_path: PathBuf,
+    start: Instant,
+    samples: VecDeque<Sample>,
+    max_samples: usize,
+    sample_seconds: f32,
+    known_spacecrafts: HashSet<GameObjectId>,
+    losses: usize,
+    mined_tracker: MinedTracker,
+    selected: BTreeSet<String>,
+    export_status: Option<String>,
+    sample_interval: Interval,
+    interval: Interval,
+}
+
+impl Telemetry {
+    pub fn new(export_path: PathBuf) -> Self {
+        Self {
+            export_path,
+            start: Instant::now(),
+            samples: VecDeque::new(),
+            max_samples: 3600,
+            sample_seconds: 1.,
+            known_spacecrafts: HashSet::new(),
+            losses: 0,
+            mined_tracker: MinedTracker::default(),
+            selected: BTreeSet::from(["ships".to_string()]),
+            export_status: None,
+            sample_interval: Interval::new(time::Duration::from_secs(1)),
+            interval: Interval::new(POLL_INTERVAL),
+        }
+    }
+
+    fn sample(&mut self, game_data: &GameData) -> Sample {
+        let mut values = BTreeMap::new();
+        let time = self.start.elapsed().as_secs_f64();
+
+        for (material, amount) in &game_data.player().materials {
+            values.insert(format!("material/{:?}", material), *amount as f64);
+        }
+        // Income is the net change per second since the previous sample, mined only counts what came in
+        let mined = self.mined_tracker.take();
+        if let Some(previous) = self.samples.back() {
+            let dt = (time - previous.time).max(1e-3);
+            for (material, amount) in &game_data.player().materials {
+                let key = format!("material/{:?}", material);
+                if let Some(before) = previous.values.get(&key) {
+                    values.insert(format!("income/{:?}", material), (*amount as f64 - before) / dt);
+                }
+            }
+            for (material, amount) in mined {
+                values.insert(format!("mined/{:?}", material), amount as f64 / dt);
+            }
+        }
+
+        let spacecrafts = game_data.my_spacecrafts();
+        values.insert("ships".into(), spacecrafts.len() as f64);
+        let mut by_tag: BTreeMap<&String, usize> = BTreeMap::new();
+        for spacecraft in spacecrafts.values() {
+            for tag in &spacecraft.tags {
+                *by_tag.entry(tag).or_default() += 1;
+            }
+        }
+        for (tag, count) in by_tag {
+            values.insert(format!("ships/{}", tag), count as f64);
+        }
+
+        self.losses += self.known_spacecrafts.iter().filter(|id| !spacecrafts.contains_key(id)).count();
+        self.known_spacecrafts = spacecrafts.keys().copied().collect();
+        values.insert("losses".into(), self.losses as f64);
+
+        let (mut hangars, mut busy) = (0, 0);
+        for star_base in game_data.my_star_bases().values() {
+            hangars += star_base.hangars.len();
+            busy += star_base.hangars.iter().filter(|hangar| !hangar.building_queue.is_empty()).count();
+        }
+        if hangars > 0 {
+            values.insert("hangar utilization".into(), busy as f64 / hangars as f64);
+        }
+
+        Sample { time, values }
+    }
+
+    fn series_names(&self) -> BTreeSet<String> {
+        self.samples.iter().flat_map(|sample| sample.values.keys().cloned()).collect()
+    }
+
+    fn export_csv(&self) -> anyhow::Result<PathBuf> {
+        let series = self.series_names();
+        let mut csv = csv_field("time");
+        for name in &series {
+            write!(csv, ",{}", csv_field(name))?;
+        }
+        csv.push('\n');
+        for sample in &self.samples {
+            write!(csv, "{:.3}", sample.time)?;
+            for name in &series {
+                match sample.values.get(name) {
+                    Some(value) => write!(csv, ",{}", value)?,
+                    None => csv.push(','),
+                }
+            }
+            csv.push('\n');
+        }
+
+        std::fs::create_dir_all(&self.export_path)?;
+        let unix_time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+        let path = self.export_path.join(format!("telemetry_{}.csv", unix_time));
+        std::fs::write(&path, csv)?;
+        Ok(path)
+    }
+}
+
+impl Plugin for Telemetry {
+    fn name(&self) -> String {
+        "telemetry".into()
+    }
+
+    fn update(&mut self, game_data: &mut GameData) {
+        self.mined_tracker.track(&game_data.player().materials);
+        if !self.sample_interval.check() {
+            return;
+        }
+        let sample = self.sample(game_data);
+        self.samples.push_back(sample);
+        while self.samples.len() > self.max_samples {
+            self.samples.pop_front();
+        }
+    }
+
+    fn update_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.add(egui::DragValue::new(&mut self.sample_seconds).speed(0.1).clamp_range(0.1..=60.).prefix("Sample every ").suffix(" s")).changed() {
+                self.sample_interval = Interval::new(time::Duration::from_secs_f32(self.sample_seconds));
+            }
+            ui.add(egui::DragValue::new(&mut self.max_samples).clamp_range(10..=100_000).prefix("Keep "));
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Export CSV").clicked() {
+                self.export_status = Some(match self.export_csv() {
+                    Ok(path) => format!("Exported to {:?}", path),
+                    Err(err) => format!("Export failed: {}", err),
+                });
+            }
+            if ui.button("Clear").clicked() {
+                self.samples.clear();
+                self.losses = 0;
+                self.mined_tracker.take();
+            }
+            ui.label(format!("{} samples", self.samples.len()));
+        });
+        if let Some(status) = &self.export_status {
+            ui.label(status);
+        }
+
+        ui.collapsing("Series", |ui| {
+            for name in self.series_names() {
+                let mut shown = self.selected.contains(&name);
+                if ui.checkbox(&mut shown, &name).changed() {
+                    if shown {
+                        self.selected.insert(name);
+                    } else {
+                        self.selected.remove(&name);
+                    }
+                }
+            }
+        });
+
+        Plot::new("telemetry")
+            .legend(Legend::default())
+            .height(250.)
+            .show(ui, |plot_ui| {
+                for name in &self.selected {
+                    let points: PlotPoints = self.samples.iter()
+                        .filter_map(|sample| Some([sample.time, *sample.values.get(name)?]))
+                        .collect();
+                    plot_ui.line(Line::new(points).name(name));
+                }
+            });
+    }
+
+    fn update_interval(&mut self) -> &mut Interval {
+        &mut self.interval
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
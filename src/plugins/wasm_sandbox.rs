@@ -0,0 +1,270 @@
+use std::{collections::VecDeque, fs, path::PathBuf};
+
+use wasmi::{Caller, Config, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use super::*;
+
+use watched_dir::{WatchedDir, has_extension};
+
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+const MAX_OUTPUT_SIZE: usize = 1024 * 1024;
+
+struct HostState {
+    limits: StoreLimits,
+    log: Vec<String>,
+}
+
+/// The module mirrors the wasm32 `execute(game_bin, user_bin) -> Vec<u8>` of this crate with plain wasm types:
+/// it exports `memory`, `alloc(len: i32) -> i32` and `execute(game_ptr, game_len, user_ptr, user_len) -> i64`,
+/// where the result packs the pointer of the serialized `Vec<GameCmd>` in the high and its length in the low 32 bits.
+/// It may import `env.log(ptr: i32, len: i32)` to print UTF-8 text.
+struct SandboxedModule {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    execute: TypedFunc<(i32, i32, i32, i32), i64>,
+    fuel_used: u64,
+}
+
+impl SandboxedModule {
+    fn instantiate(engine: &wasmi::Engine, module: &Module, memory_pages: usize, fuel: u64) -> anyhow::Result<Self> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(memory_pages * WASM_PAGE_SIZE)
+            .instances(1)
+            .memories(1)
+            .tables(1)
+            .build();
+        let mut store = Store::new(engine, HostState { limits, log: vec![] });
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(fuel).map_err(|err| anyhow::anyhow!("{}", err))?;
+
+        let mut linker = <Linker<HostState>>::new(engine);
+        linker.func_wrap("env", "log", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            let Some(memory) = caller.get_export("memory").and_then(|export| export.into_memory()) else {
+                return;
+            };
+            let mut buffer = vec![0; (len.max(0) as usize).min(1024)];
+            if memory.read(&caller, ptr as usize, &mut buffer).is_ok() {
+                let text = String::from_utf8_lossy(&buffer).to_string();
+                caller.data_mut().log.push(text);
+            }
+        })?;
+
+        let instance: Instance = linker.instantiate(&mut store, module)?.start(&mut store)?;
+        let memory = instance.get_memory(&store, "memory").ok_or(anyhow::anyhow!("module does not export memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc")?;
+        let execute = instance.get_typed_func::<(i32, i32, i32, i32), i64>(&store, "execute")?;
+
+        Ok(Self {
+            store,
+            memory,
+            alloc,
+            execute,
+            fuel_used: 0,
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> anyhow::Result<(i32, i32)> {
+        let len = i32::try_from(data.len())?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory.write(&mut self.store, ptr as u32 as usize, data)?;
+        Ok((ptr, len))
+    }
+
+    fn execute(&mut self, game_bin: &[u8], user_bin: &[u8], fuel: u64) -> anyhow::Result<Vec<GameCmd>> {
+        self.store.set_fuel(fuel).map_err(|err| anyhow::anyhow!("{}", err))?;
+        let (game_ptr, game_len) = self.write(game_bin)?;
+        let (user_ptr, user_len) = self.write(user_bin)?;
+        let result = self.execute.call(&mut self.store, (game_ptr, game_len, user_ptr, user_len));
+        self.fuel_used = fuel - self.store.get_fuel().unwrap_or(0);
+        let packed = result? as u64;
+
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        if len > MAX_OUTPUT_SIZE {
+            anyhow::bail!("output of {} bytes exceeds {}", len, MAX_OUTPUT_SIZE);
+        }
+        let mut output = vec![0; len];
+        self.memory.read(&self.store, ptr, &mut output)?;
+        Ok(deserialize_bytes(&output)?)
+    }
+}
+
+struct WasmFile {
+    name: String,
+    module: Result<Module, String>,
+    instance: Option<SandboxedModule>,
+    enabled: bool,
+    error: Option<String>,
+    log: VecDeque<String>,
+    rejected_cmds: usize,
+}
+
+pub struct WasmSandbox {
+    dir: WatchedDir,
+    engine: wasmi::Engine,
+    files: BTreeMap<PathBuf, WasmFile>,
+    fuel_per_tick: u64,
+    memory_pages: usize,
+    instances_memory_pages: usize,
+    error: Option<String>,
+    scan_interval: Interval,
+    interval: Interval,
+}
+
+impl WasmSandbox {
+    pub fn new(wasm_plugins_path: PathBuf) -> Self {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        Self {
+            dir: WatchedDir::new(wasm_plugins_path),
+            engine: wasmi::Engine::new(&config),
+            files: BTreeMap::new(),
+            fuel_per_tick: 50_000_000,
+            memory_pages: 256,
+            instances_memory_pages: 256,
+            error: None,
+            scan_interval: Interval::new(time::Duration::from_secs(2)),
+            interval: Interval::new(time::Duration::from_millis(300)),
+        }
+    }
+
+    fn scan(&mut self) -> anyhow::Result<()> {
+        let changes = self.dir.scan(|path| has_extension(path, "wasm"))?;
+        for module_path in changes.changed {
+            let enabled = self.files.get(&module_path).is_some_and(|file| file.enabled);
+            let module = fs::read(&module_path)
+                .map_err(|err| err.to_string())
+                .and_then(|bytes| Module::new(&self.engine, &bytes[..]).map_err(|err| err.to_string()));
+            let name = module_path.file_stem().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            self.files.insert(module_path, WasmFile {
+                name,
+                module,
+                instance: None,
+                enabled,
+                error: None,
+                log: VecDeque::new(),
+                rejected_cmds: 0,
+            });
+        }
+        for module_path in changes.removed {
+            self.files.remove(&module_path);
+        }
+        Ok(())
+    }
+}
+
+impl Plugin for WasmSandbox {
+    fn name(&self) -> String {
+        "wasm sandbox".into()
+    }
+
+    fn update(&mut self, game_data: &mut GameData) {
+        if self.scan_interval.check() {
+            self.error = None;
+            if let Err(err) = self.scan() {
+                self.error = Some(format!("Could not read {:?}: {}", self.dir.path(), err));
+            }
+        }
+        if !self.files.values().any(|file| file.enabled) {
+            return;
+        }
+        // The memory limit is fixed per store, so running instances are restarted with the new one
+        if self.memory_pages != self.instances_memory_pages {
+            for file in self.files.values_mut() {
+                file.instance = None;
+            }
+            self.instances_memory_pages = self.memory_pages;
+        }
+
+        let game_bin = match serialize_bytes(&*game_data.game) {
+            Ok(game_bin) => game_bin,
+            Err(err) => {
+                self.error = Some(format!("Could not serialize the game: {}", err));
+                return;
+            }
+        };
+        let user_bin = serialize_bytes(&User::Player(game_data.player_id)).unwrap_or_default();
+
+        for file in self.files.values_mut() {
+            let Ok(module) = &file.module else {
+                continue;
+            };
+            if !file.enabled {
+                continue;
+            }
+            if file.instance.is_none() {
+                match SandboxedModule::instantiate(&self.engine, module, self.memory_pages, self.fuel_per_tick) {
+                    Ok(instance) => file.instance = Some(instance),
+                    Err(err) => {
+                        file.error = Some(format!("Could not instantiate: {}", err));
+                        file.enabled = false;
+                        continue;
+                    }
+                }
+            }
+            let instance = file.instance.as_mut().unwrap();
+            let result = instance.execute(&game_bin, &user_bin, self.fuel_per_tick);
+            for line in instance.store.data_mut().log.drain(..) {
+                file.log.push_back(line);
+            }
+            while file.log.len() > 100 {
+                file.log.pop_front();
+            }
+            match result {
+                Ok(cmds) => {
+                    file.error = None;
+                    for cmd in cmds {
+                        if game_data.try_execute_cmd(cmd).is_err() {
+                            file.rejected_cmds += 1;
+                        }
+                    }
+                }
+                Err(err) => {
+                    file.error = Some(err.to_string());
+                    file.instance = None;
+                }
+            }
+        }
+    }
+
+    fn update_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Modules from {:?}", self.dir.path()));
+        ui.add(egui::DragValue::new(&mut self.fuel_per_tick).speed(100_000).clamp_range(1..=u64::MAX).prefix("Fuel per tick: "));
+        ui.add(egui::DragValue::new(&mut self.memory_pages).clamp_range(1..=16384).prefix("Memory pages (64 KiB): "));
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        for file in self.files.values_mut() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut file.enabled, &file.name);
+                if let Some(instance) = &file.instance {
+                    ui.weak(format!("fuel used: {}", instance.fuel_used));
+                }
+                if ui.small_button("Restart").clicked() {
+                    file.instance = None;
+                    file.error = None;
+                }
+            });
+            if let Err(err) = &file.module {
+                ui.colored_label(egui::Color32::RED, err);
+            } else if let Some(err) = &file.error {
+                ui.colored_label(egui::Color32::YELLOW, err);
+            }
+            if file.rejected_cmds > 0 {
+                ui.colored_label(egui::Color32::YELLOW, format!("Rejected commands: {}", file.rejected_cmds));
+            }
+            if !file.log.is_empty() {
+                ui.collapsing(format!("{} output", file.name), |ui| {
+                    for line in &file.log {
+                        ui.monospace(line);
+                    }
+                });
+            }
+        }
+    }
+
+    fn update_interval(&mut self) -> &mut Interval {
+        &mut self.interval
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::VecDeque, time::{Duration, Instant}};
+
+use super::*;
+
+const SAMPLES: usize = 60;
+
+#[derive(Debug, Default)]
+pub struct TimingStats {
+    samples: VecDeque<Duration>,
+    pub worst: Duration,
+}
+
+impl TimingStats {
+    fn record(&mut self, duration: Duration) {
+        self.samples.push_back(duration);
+        if self.samples.len() > SAMPLES {
+            self.samples.pop_front();
+        }
+        self.worst = self.worst.max(duration);
+    }
+
+    pub fn last(&self) -> Duration {
+        self.samples.back().copied().unwrap_or_default()
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PluginProfile {
+    pub update: TimingStats,
+    pub update_ui: TimingStats,
+    pub over_budget: usize,
+    penalty: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetAction {
+    Warn,
+    Skip,
+}
+
+pub struct Profiler {
+    profiles: BTreeMap<String, PluginProfile>,
+    pub plugin_budget: Duration,
+    pub action: BudgetAction,
+    pub frame_budget: Duration,
+    frame_used: Duration,
+    deferred: usize,
+    /// Plugin the frame starts at, the first one deferred last frame
+    first: usize,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            profiles: BTreeMap::new(),
+            plugin_budget: Duration::from_millis(4),
+            action: BudgetAction::Warn,
+            frame_budget: Duration::from_millis(12),
+            frame_used: Duration::ZERO,
+            deferred: 0,
+            first: 0,
+        }
+    }
+}
+
+impl Profiler {
+    pub fn start_frame(&mut self, keys: &[String]) -> impl Iterator<Item = usize> {
+        self.profiles.retain(|key, _| keys.contains(key));
+        if self.deferred == 0 {
+            self.first = 0;
+        }
+        self.frame_used = Duration::ZERO;
+        self.deferred = 0;
+        let count = keys.len();
+        let first = if count == 0 { 0 } else { self.first % count };
+        (0..count).map(move |offset| (first + offset) % count)
+    }
+
+    pub fn defer(&mut self, index: usize) -> bool {
+        let exhausted = self.frame_used >= self.frame_budget;
+        if exhausted {
+            if self.deferred == 0 {
+                self.first = index;
+            }
+            self.deferred += 1;
+        }
+        exhausted
+    }
+
+    pub fn skip(&mut self, key: &str) -> bool {
+        let Some(profile) = self.profiles.get_mut(key) else {
+            return false;
+        };
+        if profile.penalty == 0 {
+            return false;
+        }
+        profile.penalty -= 1;
+        true
+    }
+
+    pub fn measure_update<T>(&mut self, key: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let duration = start.elapsed();
+        self.frame_used += duration;
+
+        let profile = self.profiles.entry(key.to_string()).or_default();
+        profile.update.record(duration);
+        if duration > self.plugin_budget {
+            profile.over_budget += 1;
+            if self.action == BudgetAction::Skip {
+                profile.penalty = (duration.as_secs_f32() / self.plugin_budget.as_secs_f32().max(1e-6)) as u32;
+            }
+        }
+        result
+    }
+
+    pub fn measure_ui<T>(&mut self, key: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.profiles.entry(key.to_string()).or_default().update_ui.record(start.elapsed());
+        result
+    }
+
+    pub fn summary_ui(&self, ui: &mut egui::Ui, key: &str) {
+        let Some(profile) = self.profiles.get(key) else {
+            return;
+        };
+        let text = format!("{:.1} ms", profile.update.average().as_secs_f32() * 1000.);
+        if profile.update.average() > self.plugin_budget {
+            ui.colored_label(egui::Color32::RED, text);
+        } else if profile.update.last() > self.plugin_budget {
+            ui.colored_label(egui::Color32::YELLOW, text);
+        } else {
+            ui.weak(text);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut plugin_budget = self.plugin_budget.as_secs_f32() * 1000.;
+        let mut frame_budget = self.frame_budget.as_secs_f32() * 1000.;
+        ui.add(egui::DragValue::new(&mut plugin_budget).speed(0.1).clamp_range(0.1..=1000.).prefix("Plugin budget: ").suffix(" ms"));
+        ui.add(egui::DragValue::new(&mut frame_budget).speed(0.1).clamp_range(0.1..=1000.).prefix("Frame budget: ").suffix(" ms"));
+        self.plugin_budget = Duration::from_secs_f32(plugin_budget / 1000.);
+        self.frame_budget = Duration::from_secs_f32(frame_budget / 1000.);
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.action, BudgetAction::Warn, "Warn");
+            ui.radio_value(&mut self.action, BudgetAction::Skip, "Skip");
+        });
+        ui.label(format!(
+            "Last frame: {:.1} ms in updates, {} deferred",
+            self.frame_used.as_secs_f32() * 1000.,
+            self.deferred,
+        ));
+        if ui.button("Reset worst").clicked() {
+            for profile in self.profiles.values_mut() {
+                profile.update.worst = Duration::ZERO;
+                profile.update_ui.worst = Duration::ZERO;
+                profile.over_budget = 0;
+            }
+        }
+
+        egui::Grid::new("profiler").striped(true).show(ui, |ui| {
+            ui.strong("plugin");
+            ui.strong("update avg");
+            ui.strong("update max");
+            ui.strong("ui avg");
+            ui.strong("ui max");
+            ui.strong("over budget");
+            ui.end_row();
+            let ms = |duration: Duration| format!("{:.2}", duration.as_secs_f32() * 1000.);
+            for (key, profile) in &self.profiles {
+                ui.label(key);
+                ui.label(ms(profile.update.average()));
+                ui.label(ms(profile.update.worst));
+                ui.label(ms(profile.update_ui.average()));
+                ui.label(ms(profile.update_ui.worst));
+                ui.label(profile.over_budget.to_string());
+                ui.end_row();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn deferred_plugin_goes_first_next_frame() {
+        let mut profiler = Profiler::default();
+        let keys = keys(&["a", "b", "c"]);
+
+        assert_eq!(profiler.start_frame(&keys).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert!(!profiler.defer(0));
+        profiler.frame_used = profiler.frame_budget;
+        assert!(profiler.defer(1));
+        assert!(profiler.defer(2));
+
+        assert_eq!(profiler.start_frame(&keys).collect::<Vec<_>>(), vec![1, 2, 0]);
+        for index in [1, 2, 0] {
+            assert!(!profiler.defer(index));
+        }
+
+        assert_eq!(profiler.start_frame(&keys).collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn start_frame_handles_shrinking_list() {
+        let mut profiler = Profiler::default();
+        profiler.start_frame(&keys(&["a", "b", "c"])).for_each(drop);
+        profiler.frame_used = profiler.frame_budget;
+        profiler.defer(2);
+
+        assert_eq!(profiler.start_frame(&keys(&["a", "b"])).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(profiler.start_frame(&[]).count(), 0);
+    }
+
+    #[test]
+    fn profiles_of_removed_plugins_are_dropped() {
+        let mut profiler = Profiler::default();
+        profiler.measure_ui("a", || ());
+        profiler.measure_update("removed", || ());
+
+        profiler.start_frame(&keys(&["a", "b"])).for_each(drop);
+        assert_eq!(profiler.profiles.keys().collect::<Vec<_>>(), vec!["a"]);
+    }
+}
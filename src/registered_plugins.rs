@@ -0,0 +1,11 @@
+/// Plugins the plugin manager starts with, also listed in the build manifest by `build.rs`
+pub const PLUGINS: [&str; 8] = [
+    "SpacecraftControl",
+    "SpacecraftConstruction",
+    "StructureOptimizer",
+    "ScriptRunner",
+    "WasmSandbox",
+    "Telemetry",
+    "DebugOverlay",
+    "Minimap",
+];
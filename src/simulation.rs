@@ -0,0 +1,148 @@
+use std::cell::Cell;
+
+use super::*;
+
+use utils::{predictive_shoot_at, improved_fly_to};
+
+const STEP: f32 = 1. / 20.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Scenario {
+    Combat,
+    Mining,
+}
+
+thread_local! {
+    /// Set while this thread steps a simulation, the shared helpers then neither draw nor log
+    static RUNNING: Cell<bool> = Cell::new(false);
+}
+
+pub fn running() -> bool {
+    RUNNING.with(|running| running.get())
+}
+
+struct Running(bool);
+
+impl Running {
+    fn start() -> Self {
+        Self(RUNNING.with(|running| running.replace(true)))
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        RUNNING.with(|running| running.set(self.0));
+    }
+}
+
+pub struct Snapshot(Vec<u8>);
+
+impl Snapshot {
+    pub fn new(game: &Game) -> anyhow::Result<Self> {
+        Ok(Self(serialize_bytes(game)?))
+    }
+}
+
+pub struct Simulation {
+    game: Game,
+    player_id: PlayerId,
+}
+
+impl Simulation {
+    pub fn new(snapshot: &Snapshot, player_id: PlayerId) -> anyhow::Result<Self> {
+        Ok(Self { game: deserialize_bytes(&snapshot.0)?, player_id })
+    }
+
+    fn my_spacecraft_ids(&self) -> Vec<GameObjectId> {
+        self.game.game_objects.iter().filter_map(|(id, game_object)| match game_object {
+            GameObject::Spacecraft(spacecraft) if spacecraft.owner == self.player_id => Some(*id),
+            _ => None,
+        }).collect()
+    }
+
+    fn enemy_components(&self) -> usize {
+        self.game.game_objects.values().map(|game_object| match game_object {
+            GameObject::Spacecraft(spacecraft) if spacecraft.owner != self.player_id => spacecraft.components.len(),
+            _ => 0,
+        }).sum()
+    }
+
+    fn total_materials(&self) -> f32 {
+        self.game.players.get(&self.player_id).map(|player| player.materials.values().sum()).unwrap_or(0.)
+    }
+
+    fn step(&mut self) {
+        self.game.update(STEP);
+    }
+
+    fn spawn(&mut self, structure: &SpacecraftStructure) -> Result<GameObjectId, String> {
+        let (star_base_id, _) = self.game.game_objects.iter()
+            .find(|(_, game_object)| matches!(game_object, GameObject::StarBase(star_base) if star_base.owner == self.player_id))
+            .ok_or("no own star base to build at")?;
+        let star_base_id = *star_base_id;
+
+        if let Some(GameObject::StarBase(star_base)) = self.game.game_objects.get_mut(&star_base_id) {
+            let hangar = star_base.hangars.first_mut().ok_or("star base has no hangar")?;
+            hangar.building_queue.clear();
+            hangar.progress = 0.;
+        }
+        if let Some(player) = self.game.players.get_mut(&self.player_id) {
+            for (material, amount) in structure.materials() {
+                *player.materials.entry(material).or_default() += amount;
+            }
+        }
+
+        let user = User::Player(self.player_id);
+        self.game.execute_cmd(user.clone(), GameCmd::BuildSpacecraft(star_base_id, structure.clone(), 0))
+            .map_err(|err| format!("{:?}", err))?;
+        let build_steps = (structure.build_time() / STEP).ceil() as usize + 1;
+        for _ in 0..build_steps {
+            self.step();
+        }
+
+        let before = self.my_spacecraft_ids();
+        self.game.execute_cmd(user, GameCmd::DeploySpacecraft(star_base_id, 0))
+            .map_err(|err| format!("{:?}", err))?;
+        self.my_spacecraft_ids().into_iter().find(|id| !before.contains(id)).ok_or("spacecraft was not deployed".into())
+    }
+
+    pub fn evaluate(mut self, structure: &SpacecraftStructure, scenario: Scenario, duration: f32) -> Result<f32, String> {
+        let _running = Running::start();
+        let spacecraft_id = self.spawn(structure)?;
+        let initial_components = structure.component_placeholders.len();
+        let initial_enemy_components = self.enemy_components();
+        let initial_materials = self.total_materials();
+
+        let mut cmds = vec![];
+        let mut shared = SharedState::default();
+        for _ in 0..(duration / STEP) as usize {
+            let mut game_data = GameData::new(&mut self.game, self.player_id, &mut cmds, &mut shared);
+            let Some(GameObject::Spacecraft(spacecraft)) = game_data.game_objects.get(&spacecraft_id).cloned() else {
+                break;
+            };
+            let position = spacecraft.body.position;
+            let target = match scenario {
+                // Only enemies count towards the score, so asteroids are never targeted
+                Scenario::Combat => game_data.closest_enemy_spacecraft(&position).map(|(_, enemy)| enemy.body)
+                    .or_else(|| game_data.closest_enemy_star_base(&position).map(|(_, star_base)| star_base.body)),
+                Scenario::Mining => game_data.closest_asteroid(&position).map(|(_, asteroid)| asteroid.body),
+            };
+            if let Some(target) = target {
+                game_data.execute_cmds(predictive_shoot_at((&spacecraft_id, &spacecraft), target.clone()));
+                game_data.execute_cmds(improved_fly_to((&spacecraft_id, &spacecraft), target));
+            }
+            cmds.clear();
+            self.step();
+        }
+
+        let remaining_components = match self.game.game_objects.get(&spacecraft_id) {
+            Some(GameObject::Spacecraft(spacecraft)) => spacecraft.components.len(),
+            _ => 0,
+        };
+        let survival = remaining_components as f32 / initial_components.max(1) as f32;
+        Ok(match scenario {
+            Scenario::Combat => initial_enemy_components.saturating_sub(self.enemy_components()) as f32 + survival * 10.,
+            Scenario::Mining => (self.total_materials() - initial_materials) * survival,
+        })
+    }
+}
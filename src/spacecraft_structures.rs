@@ -17,4 +17,4 @@ pub fn asteroid_miner() -> SpacecraftStructure {
         ],
         tags: vec!["asteroid_miner".to_string()],
     }
-}
\ No newline at end of file
+}
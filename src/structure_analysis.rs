@@ -0,0 +1,150 @@
+use std::{mem::Discriminant, sync::Mutex};
+
+use super::*;
+
+use utils::optimal_thrust;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ComponentStats {
+    pub mass: f32,
+    pub thrust: f32,
+    pub weapon: bool,
+}
+
+static COMPONENT_STATS: Mutex<Vec<(Discriminant<ComponentType>, ComponentStats)>> = Mutex::new(vec![]);
+
+pub fn component_stats(component_type: &ComponentType) -> ComponentStats {
+    let key = std::mem::discriminant(component_type);
+    let mut cache = COMPONENT_STATS.lock().unwrap();
+    if let Some((_, stats)) = cache.iter().find(|(x, _)| *x == key) {
+        return *stats;
+    }
+    let component = ComponentPlaceholder::new(component_type.clone(), IVec2::ZERO, Orientation::Up).build();
+    let stats = ComponentStats {
+        mass: component.mass(),
+        thrust: match &component {
+            Component::Engine(engine) => engine.thrust,
+            _ => 0.,
+        },
+        weapon: matches!(component, Component::Weapon(_)),
+    };
+    cache.push((key, stats));
+    stats
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructureAnalysis {
+    pub materials: Vec<(Material, f32)>,
+    pub build_time: f32,
+    pub mass: f32,
+    pub center_of_mass: Vec2,
+    pub thrust_direction: Vec2,
+    pub thrust: f32,
+    pub max_acceleration: f32,
+    pub max_angular_acceleration: f32,
+    /// Torque with every engine at full power, zero for a balanced design
+    pub net_torque: f32,
+    pub weapons: usize,
+    /// Fraction of directions at least one weapon can fire in without hitting the hull
+    pub weapon_coverage: f32,
+}
+
+impl StructureAnalysis {
+    pub fn new(structure: &SpacecraftStructure) -> Self {
+        let placeholders = &structure.component_placeholders;
+        let center = |placeholder: &ComponentPlaceholder| placeholder.position.as_vec2() + Vec2::splat(0.5);
+
+        let mass = placeholders.iter().map(|x| component_stats(&x.component_type).mass).sum::<f32>();
+        let center_of_mass = if mass > 0. {
+            placeholders.iter().map(|x| center(x) * component_stats(&x.component_type).mass).sum::<Vec2>() / mass
+        } else {
+            Vec2::ZERO
+        };
+
+        let engines = placeholders.iter().filter_map(|x| {
+            let stats = component_stats(&x.component_type);
+            (stats.thrust > 0.).then(|| (x, Vec2::from_angle(x.orientation.to_radians()), stats.thrust))
+        }).collect::<Vec<_>>();
+        let (thrust_direction, thrust) = optimal_thrust(&engines.iter().map(|(_, direction, thrust)| (*direction, *thrust)).collect::<Vec<_>>());
+
+        // Engines can only be switched on or off, so the best torque in each direction is the sum of the helping ones
+        let torques = engines.iter().map(|(placeholder, direction, thrust)| {
+            (center(placeholder) - center_of_mass).perp_dot(*direction) * thrust
+        }).collect::<Vec<_>>();
+        let max_torque = torques.iter().filter(|x| **x > 0.).sum::<f32>()
+            .max(-torques.iter().filter(|x| **x < 0.).sum::<f32>());
+        let moment_of_inertia = placeholders.iter().map(|x| {
+            let stats = component_stats(&x.component_type);
+            stats.mass * ((center(x) - center_of_mass).length_squared() + 1. / 6.)
+        }).sum::<f32>();
+
+        let weapons = placeholders.iter().filter(|x| component_stats(&x.component_type).weapon).collect::<Vec<_>>();
+
+        Self {
+            materials: structure.materials().into_iter().collect(),
+            build_time: structure.build_time(),
+            mass,
+            center_of_mass,
+            thrust_direction,
+            thrust,
+            max_acceleration: if mass > 0. { thrust / mass } else { 0. },
+            max_angular_acceleration: if moment_of_inertia > 0. { max_torque / moment_of_inertia } else { 0. },
+            net_torque: torques.iter().sum(),
+            weapons: weapons.len(),
+            weapon_coverage: weapon_coverage(structure, &weapons),
+        }
+    }
+
+    pub fn materials_label(&self) -> String {
+        self.materials.iter().map(|(material, amount)| format!("{:?}: {:.0}", material, amount)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn weapon_coverage(structure: &SpacecraftStructure, weapons: &[&ComponentPlaceholder]) -> f32 {
+    const DIRECTIONS: usize = 36;
+
+    let hull = structure.component_placeholders.iter()
+        .filter(|x| !component_stats(&x.component_type).weapon)
+        .map(|x| x.position)
+        .collect::<Vec<_>>();
+    let reach = hull.iter().map(|x| x.x.abs().max(x.y.abs())).max().unwrap_or(0) as f32 * 2. + 2.;
+
+    let covered = (0..DIRECTIONS).filter(|i| {
+        let direction = Vec2::from_angle(*i as f32 / DIRECTIONS as f32 * 2. * PI);
+        weapons.iter().any(|weapon| {
+            let start = weapon.position.as_vec2() + Vec2::splat(0.5);
+            let mut distance = 0.5;
+            while distance < reach {
+                let cell = (start + direction * distance).floor().as_ivec2();
+                if cell != weapon.position && hull.contains(&cell) {
+                    return false;
+                }
+                distance += 0.25;
+            }
+            true
+        })
+    }).count();
+
+    covered as f32 / DIRECTIONS as f32
+}
+
+pub fn comparison_table_ui(ui: &mut egui::Ui, analyses: &[(&String, &StructureAnalysis)]) {
+    egui::Grid::new("structure comparison").striped(true).show(ui, |ui| {
+        for header in ["Structure", "Materials", "Build time", "Mass", "Thrust", "Acceleration", "Angular acc.", "Weapons", "Coverage"] {
+            ui.strong(header);
+        }
+        ui.end_row();
+        for (name, analysis) in analyses {
+            ui.label(name.as_str());
+            ui.label(analysis.materials_label());
+            ui.label(format!("{:.1}", analysis.build_time));
+            ui.label(format!("{:.1}", analysis.mass));
+            ui.label(format!("{:.0} at {:.0}°", analysis.thrust, analysis.thrust_direction.angle().to_degrees()));
+            ui.label(format!("{:.2}", analysis.max_acceleration));
+            ui.label(format!("{:.2}", analysis.max_angular_acceleration));
+            ui.label(format!("{}", analysis.weapons));
+            ui.label(format!("{:.0}%", analysis.weapon_coverage * 100.));
+            ui.end_row();
+        }
+    });
+}
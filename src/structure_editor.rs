@@ -0,0 +1,295 @@
+use std::path::{Path, PathBuf};
+
+use super::*;
+
+use structure_analysis::{StructureAnalysis, comparison_table_ui};
+use structure_formats::{StructureFormat, is_weapon};
+use structure_generator::{GeneratorParams, EnginePlacement, Symmetry};
+
+pub const COMPONENT_TYPES: [ComponentType; 4] = [
+    ComponentType::Central,
+    ComponentType::SteelBlock,
+    ComponentType::RaptorEngine,
+    ComponentType::KineticWeapon,
+];
+
+/// Cells shown around the center of an empty structure, larger structures get two cells of margin
+const MIN_GRID_RADIUS: i32 = 5;
+const CELL_SIZE: f32 = 24.;
+
+fn rotate_clockwise(orientation: &Orientation) -> Orientation {
+    match orientation {
+        Orientation::Up => Orientation::Right,
+        Orientation::Right => Orientation::Down,
+        Orientation::Down => Orientation::Left,
+        Orientation::Left => Orientation::Up,
+    }
+}
+
+fn component_color(component_type: &ComponentType) -> egui::Color32 {
+    match component_type {
+        ComponentType::Central => egui::Color32::GOLD,
+        ComponentType::SteelBlock => egui::Color32::GRAY,
+        ComponentType::RaptorEngine => egui::Color32::LIGHT_RED,
+        ComponentType::KineticWeapon => egui::Color32::LIGHT_BLUE,
+        _ => egui::Color32::WHITE,
+    }
+}
+
+#[derive(Serialize)]
+pub struct StructureEditor {
+    structure: SpacecraftStructure,
+    component_type: ComponentType,
+    orientation: Orientation,
+    tags_input: String,
+    file_name: String,
+    format: StructureFormat,
+    status: Option<String>,
+    /// Existing file the next save overwrites, set by the first save to it
+    #[serde(skip)]
+    overwrite: Option<PathBuf>,
+    generator_params: GeneratorParams,
+    generator_size: u32,
+    #[serde(skip)]
+    analysis: Option<StructureAnalysis>,
+}
+
+impl StructureEditor {
+    pub fn new() -> Self {
+        Self {
+            structure: SpacecraftStructure {
+                component_placeholders: vec![
+                    ComponentPlaceholder::new(ComponentType::Central, ivec2(0, 0), Orientation::Up),
+                ],
+                tags: vec![],
+            },
+            component_type: ComponentType::SteelBlock,
+            orientation: Orientation::Up,
+            tags_input: String::new(),
+            file_name: "new_structure".into(),
+            format: StructureFormat::Json,
+            status: None,
+            overwrite: None,
+            generator_params: Default::default(),
+            generator_size: 1,
+            analysis: None,
+        }
+    }
+
+    pub fn load(&mut self, name: &str, structure: &SpacecraftStructure) {
+        self.structure = structure.clone();
+        self.tags_input = structure.tags.join(", ");
+        self.file_name = Path::new(name).file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+        self.format = StructureFormat::from_path(Path::new(name)).unwrap_or(StructureFormat::Json);
+        self.status = None;
+        self.analysis = None;
+    }
+
+    pub fn set_structure(&mut self, structure: SpacecraftStructure) {
+        self.tags_input = structure.tags.join(", ");
+        self.structure = structure;
+        self.analysis = None;
+    }
+
+    fn grid_radius(&self) -> i32 {
+        let extent = self.structure.component_placeholders.iter()
+            .map(|x| x.position.x.abs().max(x.position.y.abs()))
+            .max()
+            .unwrap_or(0);
+        (extent + 2).max(MIN_GRID_RADIUS)
+    }
+
+    fn cell_to_rect(origin: egui::Pos2, radius: i32, position: IVec2) -> egui::Rect {
+        let min = origin + egui::vec2((position.x + radius) as f32, (radius - position.y) as f32) * CELL_SIZE;
+        egui::Rect::from_min_size(min, egui::vec2(CELL_SIZE, CELL_SIZE))
+    }
+
+    fn pos_to_cell(origin: egui::Pos2, radius: i32, pos: egui::Pos2) -> IVec2 {
+        let offset = (pos - origin) / CELL_SIZE;
+        ivec2(offset.x.floor() as i32 - radius, radius - offset.y.floor() as i32)
+    }
+
+    fn click(&mut self, position: IVec2) {
+        let weapon_layer = is_weapon(&self.component_type);
+        let placeholders = &mut self.structure.component_placeholders;
+        let existing = placeholders.iter_mut()
+            .find(|x| x.position == position && is_weapon(&x.component_type) == weapon_layer);
+        match existing {
+            Some(existing) if existing.component_type == self.component_type => {
+                existing.orientation = rotate_clockwise(&existing.orientation);
+            }
+            Some(existing) => {
+                existing.component_type = self.component_type.clone();
+                existing.orientation = self.orientation.clone();
+            }
+            None => placeholders.push(ComponentPlaceholder::new(self.component_type.clone(), position, self.orientation.clone())),
+        }
+        self.analysis = None;
+    }
+
+    fn remove(&mut self, position: IVec2) {
+        let placeholders = &mut self.structure.component_placeholders;
+        // Weapons sit on top of the hull, so they go first
+        let index = placeholders.iter().position(|x| x.position == position && is_weapon(&x.component_type))
+            .or_else(|| placeholders.iter().position(|x| x.position == position));
+        if let Some(index) = index {
+            placeholders.remove(index);
+            self.analysis = None;
+        }
+    }
+
+    fn grid_ui(&mut self, ui: &mut egui::Ui) {
+        let radius = self.grid_radius();
+        let size = egui::vec2(1., 1.) * CELL_SIZE * (radius * 2 + 1) as f32;
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::click());
+        let origin = response.rect.min;
+
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                let color = if x == 0 && y == 0 { egui::Color32::from_gray(70) } else { egui::Color32::from_gray(40) };
+                painter.rect_stroke(Self::cell_to_rect(origin, radius, ivec2(x, y)), 0., egui::Stroke::new(1., color));
+            }
+        }
+
+        let mut placeholders = self.structure.component_placeholders.iter().collect::<Vec<_>>();
+        placeholders.sort_by_key(|x| is_weapon(&x.component_type));
+        for placeholder in placeholders {
+            let rect = Self::cell_to_rect(origin, radius, placeholder.position);
+            let color = component_color(&placeholder.component_type);
+            if is_weapon(&placeholder.component_type) {
+                painter.circle_filled(rect.center(), CELL_SIZE * 0.25, color);
+            } else {
+                painter.rect_filled(rect.shrink(1.), 2., color);
+            }
+            let direction = Vec2::from_angle(placeholder.orientation.to_radians());
+            let tip = rect.center() + egui::vec2(direction.x, -direction.y) * CELL_SIZE * 0.4;
+            painter.line_segment([rect.center(), tip], egui::Stroke::new(2., egui::Color32::BLACK));
+        }
+
+        if let Some(pos) = response.interact_pointer_pos() {
+            let position = Self::pos_to_cell(origin, radius, pos);
+            if response.clicked() {
+                self.click(position);
+            } else if response.secondary_clicked() {
+                self.remove(position);
+            }
+        }
+    }
+
+    fn save(&mut self, directory: &Path) {
+        // Only plain names are accepted so saving can not write outside of the structures directory
+        let plain = self.file_name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | ' '));
+        if self.file_name.is_empty() || !plain {
+            self.status = Some("Saving failed: the file name may only contain letters, digits, spaces, '_' and '-'".into());
+            return;
+        }
+        let path = directory.join(format!("{}.{}", self.file_name, self.format.extension()));
+        if path.exists() && self.overwrite.as_ref() != Some(&path) {
+            self.status = Some(format!("{:?} already exists, save again to overwrite it", path));
+            self.overwrite = Some(path);
+            return;
+        }
+        self.overwrite = None;
+        let result = self.format.serialize(&self.structure)
+            .and_then(|raw| std::fs::write(&path, raw).map_err(|err| err.to_string()));
+        self.status = Some(match result {
+            Ok(()) => format!("Saved to {:?}", path),
+            Err(err) => format!("Saving failed: {}", err),
+        });
+    }
+
+    fn generator_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.add(egui::Slider::new(&mut self.generator_size, 1..=5).text("size"));
+            if ui.button("Scout").clicked() {
+                self.generator_params = GeneratorParams::scout(self.generator_size);
+            }
+            if ui.button("Miner").clicked() {
+                self.generator_params = GeneratorParams::miner(self.generator_size);
+            }
+            if ui.button("Gunship").clicked() {
+                self.generator_params = GeneratorParams::gunship(self.generator_size);
+            }
+        });
+        let params = &mut self.generator_params;
+        ui.add(egui::Slider::new(&mut params.hull_width, 1..=13).text("hull width"));
+        ui.add(egui::Slider::new(&mut params.hull_length, 1..=13).text("hull length"));
+        ui.add(egui::Slider::new(&mut params.engine_count, 0..=20).text("engines"));
+        ui.add(egui::Slider::new(&mut params.weapon_density, 0.0..=1.0).text("weapon density"));
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut params.engine_placement, EnginePlacement::Rear, "Rear engines");
+            ui.selectable_value(&mut params.engine_placement, EnginePlacement::Sides, "Side engines");
+        });
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut params.symmetry, Symmetry::Mirror, "Mirror");
+            ui.selectable_value(&mut params.symmetry, Symmetry::Quad, "Quad");
+        });
+        if ui.button("Generate").clicked() {
+            match structure_generator::generate(&self.generator_params) {
+                Ok(structure) => {
+                    self.file_name = self.generator_params.tags.join("_");
+                    self.set_structure(structure);
+                    self.status = None;
+                }
+                Err(err) => self.status = Some(format!("Generating failed: {}", err)),
+            }
+        }
+    }
+
+    pub fn update_ui(&mut self, ui: &mut egui::Ui, directory: &Path) {
+        ui.collapsing("Generator", |ui| {
+            self.generator_ui(ui);
+        });
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Component")
+                .selected_text(format!("{:?}", self.component_type))
+                .show_ui(ui, |ui| {
+                    for component_type in COMPONENT_TYPES {
+                        ui.selectable_value(&mut self.component_type, component_type.clone(), format!("{:?}", component_type));
+                    }
+                });
+            if ui.button(format!("Orientation: {:?}", self.orientation)).clicked() {
+                self.orientation = rotate_clockwise(&self.orientation);
+            }
+            if ui.button("Clear").clicked() {
+                self.structure.component_placeholders.clear();
+                self.analysis = None;
+            }
+        });
+
+        self.grid_ui(ui);
+
+        ui.horizontal(|ui| {
+            ui.label("Tags");
+            if ui.text_edit_singleline(&mut self.tags_input).changed() {
+                self.structure.tags = self.tags_input.split(',').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).collect();
+            }
+        });
+
+        if self.structure.valid() {
+            ui.colored_label(egui::Color32::GREEN, "Structure is valid");
+        } else {
+            ui.colored_label(egui::Color32::RED, "Structure is not valid");
+        }
+        let analysis = self.analysis.get_or_insert_with(|| StructureAnalysis::new(&self.structure));
+        comparison_table_ui(ui, &[(&self.file_name, analysis)]);
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.file_name);
+            egui::ComboBox::from_label("Format")
+                .selected_text(format!("{:?}", self.format))
+                .show_ui(ui, |ui| {
+                    for format in StructureFormat::ALL {
+                        ui.selectable_value(&mut self.format, format, format!("{:?}", format));
+                    }
+                });
+            if ui.add_enabled(self.structure.valid(), egui::Button::new("Save")).clicked() {
+                self.save(directory);
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::path::Path;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StructureFormat {
+    Json,
+    Ron,
+    Toml,
+    Blueprint,
+}
+
+impl StructureFormat {
+    pub const ALL: [StructureFormat; 4] = [
+        StructureFormat::Json,
+        StructureFormat::Ron,
+        StructureFormat::Toml,
+        StructureFormat::Blueprint,
+    ];
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::ALL.into_iter().find(|format| format.extension() == extension)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StructureFormat::Json => "json",
+            StructureFormat::Ron => "ron",
+            StructureFormat::Toml => "toml",
+            StructureFormat::Blueprint => "blueprint",
+        }
+    }
+
+    pub fn parse(&self, raw: &str) -> Result<SpacecraftStructure, String> {
+        match self {
+            StructureFormat::Json => serde_json::from_str(raw)
+                .map_err(|err| format!("line {}, column {}: {}", err.line(), err.column(), err)),
+            StructureFormat::Ron => ron::from_str(raw)
+                .map_err(|err| format!("line {}, column {}: {}", err.position.line, err.position.col, err.code)),
+            StructureFormat::Toml => toml::from_str(raw).map_err(|err| err.to_string()),
+            StructureFormat::Blueprint => parse_blueprint(raw),
+        }
+    }
+
+    pub fn serialize(&self, structure: &SpacecraftStructure) -> Result<String, String> {
+        match self {
+            StructureFormat::Json => serde_json::to_string_pretty(structure).map_err(|err| err.to_string()),
+            StructureFormat::Ron => ron::ser::to_string_pretty(structure, ron::ser::PrettyConfig::default()).map_err(|err| err.to_string()),
+            StructureFormat::Toml => toml::to_string_pretty(structure).map_err(|err| err.to_string()),
+            StructureFormat::Blueprint => serialize_blueprint(structure),
+        }
+    }
+}
+
+const EMPTY_CELL: &str = "..";
+
+fn component_char(component_type: &ComponentType) -> Option<char> {
+    match component_type {
+        ComponentType::Central => Some('C'),
+        ComponentType::SteelBlock => Some('S'),
+        ComponentType::RaptorEngine => Some('E'),
+        ComponentType::KineticWeapon => Some('W'),
+        _ => None,
+    }
+}
+
+fn char_component(c: char) -> Option<ComponentType> {
+    match c {
+        'C' => Some(ComponentType::Central),
+        'S' => Some(ComponentType::SteelBlock),
+        'E' => Some(ComponentType::RaptorEngine),
+        'W' => Some(ComponentType::KineticWeapon),
+        _ => None,
+    }
+}
+
+pub fn is_weapon(component_type: &ComponentType) -> bool {
+    matches!(component_type, ComponentType::KineticWeapon)
+}
+
+fn orientation_char(orientation: &Orientation) -> char {
+    match orientation {
+        Orientation::Up => '^',
+        Orientation::Down => 'v',
+        Orientation::Left => '<',
+        Orientation::Right => '>',
+    }
+}
+
+fn char_orientation(c: char) -> Option<Orientation> {
+    match c {
+        '^' => Some(Orientation::Up),
+        'v' => Some(Orientation::Down),
+        '<' => Some(Orientation::Left),
+        '>' => Some(Orientation::Right),
+        _ => None,
+    }
+}
+
+/// Parses a blueprint of the form
+///
+/// ```text
+/// tags: medium, miner
+/// origin: -1 1
+/// hull:
+/// ..S^..
+/// S^C^S^
+/// E^..E^
+/// weapons:
+/// ..W^..
+/// ```
+///
+/// Every cell is a component character followed by an orientation marker (`^`, `v`, `<`, `>`),
+/// `..` is empty. `origin` is the position of the top left cell, rows go downwards.
+pub fn parse_blueprint(raw: &str) -> Result<SpacecraftStructure, String> {
+    let mut tags = vec![];
+    let mut origin = ivec2(0, 0);
+    let mut layers: [Vec<(usize, &str)>; 2] = [vec![], vec![]];
+    let mut layer = None;
+
+    for (line_index, line) in raw.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.trim_end();
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("tags:") {
+            tags = value.split(',').map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect();
+        } else if let Some(value) = line.strip_prefix("origin:") {
+            let coordinates = value.split_whitespace().map(|x| x.parse::<i32>()).collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("line {}: {}", line_number, err))?;
+            let [x, y] = coordinates[..] else {
+                return Err(format!("line {}: origin needs two coordinates", line_number));
+            };
+            origin = ivec2(x, y);
+        } else if line == "hull:" {
+            layer = Some(0);
+        } else if line == "weapons:" {
+            layer = Some(1);
+        } else if let Some(layer) = layer {
+            layers[layer].push((line_number, line));
+        } else {
+            return Err(format!("line {}: unexpected {:?}", line_number, line));
+        }
+    }
+
+    let mut component_placeholders = vec![];
+    for rows in &layers {
+        for (row, (line_number, line)) in rows.iter().enumerate() {
+            let chars = line.chars().collect::<Vec<_>>();
+            if chars.len() % 2 != 0 {
+                return Err(format!("line {}: cells are two characters wide", line_number));
+            }
+            for (column, cell) in chars.chunks(2).enumerate() {
+                if cell.iter().collect::<String>() == EMPTY_CELL {
+                    continue;
+                }
+                let component_type = char_component(cell[0])
+                    .ok_or_else(|| format!("line {}: unknown component {:?}", line_number, cell[0]))?;
+                let orientation = char_orientation(cell[1])
+                    .ok_or_else(|| format!("line {}: unknown orientation {:?}", line_number, cell[1]))?;
+                let position = origin + ivec2(column as i32, -(row as i32));
+                component_placeholders.push(ComponentPlaceholder::new(component_type, position, orientation));
+            }
+        }
+    }
+
+    Ok(SpacecraftStructure {
+        component_placeholders,
+        tags,
+    })
+}
+
+pub fn serialize_blueprint(structure: &SpacecraftStructure) -> Result<String, String> {
+    let placeholders = &structure.component_placeholders;
+    if placeholders.is_empty() {
+        return Ok(format!("tags: {}\n", structure.tags.join(", ")));
+    }
+    let min_x = placeholders.iter().map(|x| x.position.x).min().unwrap();
+    let max_x = placeholders.iter().map(|x| x.position.x).max().unwrap();
+    let min_y = placeholders.iter().map(|x| x.position.y).min().unwrap();
+    let max_y = placeholders.iter().map(|x| x.position.y).max().unwrap();
+    let width = (max_x - min_x + 1) as usize;
+    let height = (max_y - min_y + 1) as usize;
+
+    let mut layers = [vec![vec![EMPTY_CELL.to_string(); width]; height], vec![vec![EMPTY_CELL.to_string(); width]; height]];
+    for placeholder in placeholders {
+        let c = component_char(&placeholder.component_type)
+            .ok_or_else(|| format!("{:?} has no blueprint character", placeholder.component_type))?;
+        let layer = &mut layers[is_weapon(&placeholder.component_type) as usize];
+        let cell = &mut layer[(max_y - placeholder.position.y) as usize][(placeholder.position.x - min_x) as usize];
+        if cell != EMPTY_CELL {
+            return Err(format!("two components of the same layer at {:?}", placeholder.position));
+        }
+        *cell = format!("{}{}", c, orientation_char(&placeholder.orientation));
+    }
+
+    let mut result = String::new();
+    result += &format!("tags: {}\n", structure.tags.join(", "));
+    result += &format!("origin: {} {}\n", min_x, max_y);
+    for (name, layer) in ["hull", "weapons"].into_iter().zip(layers) {
+        result += &format!("{}:\n", name);
+        for row in layer {
+            result += &row.concat();
+            result += "\n";
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINER: &str = "\
+tags: medium, miner
+origin: -1 1
+hull:
+..S^..
+S^C^S^
+E^..E^
+weapons:
+..W^..
+";
+
+    fn cells(structure: &SpacecraftStructure) -> Vec<String> {
+        let mut cells = structure.component_placeholders.iter()
+            .map(|x| format!("{:?} {} {} {:?}", x.component_type, x.position.x, x.position.y, x.orientation))
+            .collect::<Vec<_>>();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn parses_blueprint() {
+        let structure = parse_blueprint(MINER).unwrap();
+        assert_eq!(structure.tags, vec!["medium", "miner"]);
+        assert_eq!(cells(&structure), vec![
+            "Central 0 0 Up",
+            "KineticWeapon 0 1 Up",
+            "RaptorEngine -1 -1 Up",
+            "RaptorEngine 1 -1 Up",
+            "SteelBlock -1 0 Up",
+            "SteelBlock 0 1 Up",
+            "SteelBlock 1 0 Up",
+        ]);
+    }
+
+    #[test]
+    fn blueprint_round_trip() {
+        let structure = parse_blueprint(MINER).unwrap();
+        let raw = serialize_blueprint(&structure).unwrap();
+        assert!(raw.starts_with("tags: medium, miner\norigin: -1 1\nhull:\n..S^..\nS^C^S^\nE^..E^\nweapons:\n..W^..\n"));
+        let parsed = parse_blueprint(&raw).unwrap();
+        assert_eq!(cells(&parsed), cells(&structure));
+        assert_eq!(serialize_blueprint(&parsed).unwrap(), raw);
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let structure = parse_blueprint(MINER).unwrap();
+        for format in StructureFormat::ALL {
+            let parsed = format.parse(&format.serialize(&structure).unwrap()).unwrap();
+            assert_eq!(cells(&parsed), cells(&structure), "{:?}", format);
+            assert_eq!(parsed.tags, structure.tags, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn blueprint_errors_name_the_line() {
+        let error = |raw: &str| parse_blueprint(raw).unwrap_err();
+        assert!(error("tags: a\nS^C^\n").starts_with("line 2: unexpected"));
+        assert!(error("origin: 1\nhull:\nC^\n").starts_with("line 1: origin needs two coordinates"));
+        assert!(error("hull:\nC^S\n").starts_with("line 2: cells are two characters wide"));
+        assert!(error("# comment\nhull:\n\nC^X^\n").starts_with("line 4: unknown component 'X'"));
+        assert!(error("hull:\nC^\nweapons:\nW?\n").starts_with("line 4: unknown orientation '?'"));
+    }
+}
@@ -0,0 +1,260 @@
+use super::*;
+
+use structure_analysis::StructureAnalysis;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EnginePlacement {
+    Rear,
+    Sides,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Symmetry {
+    Mirror,
+    /// Also mirrored front to back, engines still face forward
+    Quad,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratorParams {
+    /// Rounded up to an odd number so the hull is centered on the central component
+    pub hull_width: u32,
+    pub hull_length: u32,
+    pub engine_count: u32,
+    pub engine_placement: EnginePlacement,
+    pub weapon_density: f32,
+    pub symmetry: Symmetry,
+    pub tags: Vec<String>,
+}
+
+impl GeneratorParams {
+    pub fn scout(size: u32) -> Self {
+        Self {
+            hull_width: 1 + 2 * (size / 2),
+            hull_length: 1 + size,
+            engine_count: 2 + size,
+            engine_placement: EnginePlacement::Rear,
+            weapon_density: 0.2,
+            symmetry: Symmetry::Mirror,
+            tags: vec![format!("scout_{}", size)],
+        }
+    }
+
+    pub fn miner(size: u32) -> Self {
+        Self {
+            hull_width: 1 + 2 * size,
+            hull_length: 1 + size,
+            engine_count: 2 * size,
+            engine_placement: EnginePlacement::Rear,
+            weapon_density: 0.4,
+            symmetry: Symmetry::Quad,
+            tags: vec![format!("miner_{}", size)],
+        }
+    }
+
+    pub fn gunship(size: u32) -> Self {
+        Self {
+            hull_width: 1 + 2 * size,
+            hull_length: 2 + 2 * size,
+            engine_count: 2 * size,
+            engine_placement: EnginePlacement::Sides,
+            weapon_density: 0.8,
+            symmetry: Symmetry::Mirror,
+            tags: vec![format!("gunship_{}", size)],
+        }
+    }
+}
+
+impl Default for GeneratorParams {
+    fn default() -> Self {
+        Self::miner(1)
+    }
+}
+
+pub fn generate(params: &GeneratorParams) -> Result<SpacecraftStructure, String> {
+    if params.hull_width == 0 || params.hull_length == 0 {
+        return Err("hull needs to be at least 1x1".into());
+    }
+    let half_width = (params.hull_width / 2) as i32;
+    let (top, bottom) = match params.symmetry {
+        Symmetry::Mirror => {
+            let top = (params.hull_length as i32 - 1) / 2;
+            (top, top - params.hull_length as i32 + 1)
+        }
+        Symmetry::Quad => (params.hull_length as i32 / 2, -(params.hull_length as i32 / 2)),
+    };
+
+    let mut component_placeholders = vec![];
+    for y in bottom..=top {
+        for x in -half_width..=half_width {
+            let component_type = if x == 0 && y == 0 { ComponentType::Central } else { ComponentType::SteelBlock };
+            component_placeholders.push(ComponentPlaceholder::new(component_type, ivec2(x, y), Orientation::Up));
+        }
+    }
+
+    let engines = match params.symmetry {
+        Symmetry::Mirror => engine_positions(params.engine_placement, params.engine_count, half_width, top, bottom),
+        Symmetry::Quad => mirror_front_to_back(engine_positions(params.engine_placement, (params.engine_count + 1) / 2, half_width, top, bottom)),
+    };
+    for position in engines {
+        component_placeholders.push(ComponentPlaceholder::new(ComponentType::RaptorEngine, position, Orientation::Up));
+    }
+
+    for position in weapon_positions(params, half_width, top, bottom) {
+        component_placeholders.push(ComponentPlaceholder::new(ComponentType::KineticWeapon, position, Orientation::Up));
+    }
+
+    let structure = SpacecraftStructure {
+        component_placeholders,
+        tags: params.tags.clone(),
+    };
+
+    let analysis = StructureAnalysis::new(&structure);
+    if analysis.net_torque.abs() > analysis.thrust * 1e-3 {
+        return Err(format!("thrust is unbalanced, net torque {:.3}", analysis.net_torque));
+    }
+    if !structure.valid() {
+        return Err("generated structure is not valid".into());
+    }
+    Ok(structure)
+}
+
+fn mirrored_columns(count: u32, half_width: i32) -> Vec<i32> {
+    let mut columns = vec![];
+    if count % 2 == 1 {
+        columns.push(0);
+    }
+    for offset in (1..=half_width).rev().take((count / 2) as usize) {
+        columns.push(-offset);
+        columns.push(offset);
+    }
+    columns
+}
+
+fn mirror_front_to_back(positions: Vec<IVec2>) -> Vec<IVec2> {
+    let mirrored = positions.iter().filter(|position| position.y != 0).map(|position| ivec2(position.x, -position.y)).collect::<Vec<_>>();
+    positions.into_iter().chain(mirrored).collect()
+}
+
+fn engine_positions(placement: EnginePlacement, count: u32, half_width: i32, top: i32, bottom: i32) -> Vec<IVec2> {
+    let mut result = vec![];
+    let mut remaining = count;
+    match placement {
+        EnginePlacement::Rear => {
+            let mut y = bottom - 1;
+            while remaining > 0 {
+                let row = (half_width as u32 * 2 + 1).min(remaining);
+                result.extend(mirrored_columns(row, half_width).into_iter().map(|x| ivec2(x, y)));
+                remaining -= row;
+                y -= 1;
+            }
+        }
+        EnginePlacement::Sides => {
+            // Sides only hold pairs, an odd engine goes behind the hull
+            if remaining % 2 == 1 {
+                result.push(ivec2(0, bottom - 1));
+                remaining -= 1;
+            }
+            let mut y = bottom;
+            let mut x = half_width + 1;
+            while remaining > 0 {
+                result.push(ivec2(-x, y));
+                result.push(ivec2(x, y));
+                remaining -= 2;
+                y += 1;
+                if y > bottom + (top - bottom) / 2 {
+                    y = bottom;
+                    x += 1;
+                }
+            }
+        }
+    }
+    result
+}
+
+fn weapon_positions(params: &GeneratorParams, half_width: i32, top: i32, bottom: i32) -> Vec<IVec2> {
+    let cells = (half_width as u32 * 2 + 1) * (top - bottom + 1) as u32;
+    let count = (cells as f32 * params.weapon_density.clamp(0., 1.)).round() as u32;
+
+    // Outer cells first, they have the clearest line of fire
+    let mut candidates = (bottom..=top).flat_map(|y| (0..=half_width).map(move |x| ivec2(x, y))).collect::<Vec<_>>();
+    if params.symmetry == Symmetry::Quad {
+        candidates.retain(|position| position.y >= 0 || -position.y > top);
+    }
+    candidates.sort_by_key(|position| {
+        let edge_distance = (half_width - position.x).min(top - position.y).min(position.y - bottom);
+        (edge_distance, -position.y, -position.x)
+    });
+
+    let mut result = vec![];
+    for position in candidates {
+        let mut group = vec![position];
+        if position.x != 0 {
+            group.push(ivec2(-position.x, position.y));
+        }
+        if params.symmetry == Symmetry::Quad && position.y != 0 && -position.y >= bottom {
+            group.extend(group.clone().into_iter().map(|x| ivec2(x.x, -x.y)));
+        }
+        if result.len() + group.len() > count as usize {
+            continue;
+        }
+        result.extend(group);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn cells(structure: &SpacecraftStructure) -> BTreeSet<(String, i32, i32)> {
+        structure.component_placeholders.iter()
+            .map(|x| (format!("{:?}", x.component_type), x.position.x, x.position.y))
+            .collect()
+    }
+
+    fn presets() -> Vec<GeneratorParams> {
+        (1..=5).flat_map(|size| [GeneratorParams::scout(size), GeneratorParams::miner(size), GeneratorParams::gunship(size)]).collect()
+    }
+
+    #[test]
+    fn presets_are_mirrored() {
+        for params in presets() {
+            let structure = generate(&params).unwrap();
+            let structure_cells = cells(&structure);
+            let flipped = structure_cells.iter().map(|(component, x, y)| (component.clone(), -x, *y)).collect::<BTreeSet<_>>();
+            assert_eq!(flipped, structure_cells, "{:?}", params);
+            if params.symmetry == Symmetry::Quad {
+                let flipped = structure_cells.iter().map(|(component, x, y)| (component.clone(), *x, -y)).collect::<BTreeSet<_>>();
+                assert_eq!(flipped, structure_cells, "{:?}", params);
+            }
+        }
+    }
+
+    #[test]
+    fn presets_have_no_net_torque() {
+        for params in presets() {
+            let analysis = StructureAnalysis::new(&generate(&params).unwrap());
+            assert!(analysis.thrust > 0., "{:?}", params);
+            assert!(analysis.net_torque.abs() <= analysis.thrust * 1e-3, "{:?}: {}", params, analysis.net_torque);
+        }
+    }
+
+    #[test]
+    fn odd_engine_counts_stay_balanced() {
+        for engine_placement in [EnginePlacement::Rear, EnginePlacement::Sides] {
+            let params = GeneratorParams { engine_count: 3, engine_placement, ..GeneratorParams::gunship(2) };
+            let structure = generate(&params).unwrap();
+            let engines = structure.component_placeholders.iter().filter(|x| matches!(x.component_type, ComponentType::RaptorEngine));
+            assert_eq!(engines.count(), 3);
+        }
+    }
+
+    #[test]
+    fn empty_hull_is_rejected() {
+        let params = GeneratorParams { hull_width: 0, ..Default::default() };
+        assert!(generate(&params).is_err());
+    }
+}
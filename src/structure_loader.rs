@@ -0,0 +1,128 @@
+use std::{fs, io::Write, path::{Path, PathBuf}};
+
+use super::*;
+
+use watched_dir::WatchedDir;
+use structure_formats::StructureFormat;
+use structure_analysis::StructureAnalysis;
+
+#[derive(Debug, Serialize)]
+pub struct StructureFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub structure: Result<SpacecraftStructure, String>,
+    pub analysis: Option<StructureAnalysis>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StructureLoader {
+    dir: WatchedDir,
+    interval: Interval,
+    files: BTreeMap<PathBuf, StructureFile>,
+    ignored: Vec<String>,
+    error: Option<String>,
+}
+
+impl StructureLoader {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            dir: WatchedDir::new(path),
+            interval: Interval::new(time::Duration::from_secs(2)),
+            files: BTreeMap::new(),
+            ignored: vec![],
+            error: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn refresh(&mut self) {
+        if !self.interval.check() {
+            return;
+        }
+        self.error = None;
+        if let Err(err) = self.scan() {
+            self.error = Some(format!("Could not read {:?}: {}", self.dir.path(), err));
+        }
+    }
+
+    fn scan(&mut self) -> anyhow::Result<()> {
+        let changes = self.dir.scan(is_structure_file)?;
+        self.ignored = changes.ignored.iter().map(|path| file_name(path)).collect();
+        for structure_path in changes.changed {
+            let structure = fs::read_to_string(&structure_path)
+                .map_err(|err| err.to_string())
+                .and_then(|raw| parse_structure(&structure_path, &raw));
+            let analysis = structure.as_ref().ok().map(StructureAnalysis::new);
+            self.files.insert(structure_path.clone(), StructureFile { name: file_name(&structure_path), path: structure_path, structure, analysis });
+        }
+        for structure_path in changes.removed {
+            self.files.remove(&structure_path);
+        }
+        Ok(())
+    }
+
+    pub fn structures(&self) -> impl Iterator<Item = (&String, &SpacecraftStructure)> {
+        self.files.values().filter_map(|file| Some((&file.name, file.structure.as_ref().ok()?)))
+    }
+
+    pub fn structure(&self, name: &str) -> Option<&SpacecraftStructure> {
+        self.structures().find(|(structure_name, _)| *structure_name == name).map(|(_, structure)| structure)
+    }
+
+    /// Writes the structure next to its source file in another format
+    pub fn export(&self, name: &str, format: StructureFormat) -> Result<PathBuf, String> {
+        let file = self.files.values().find(|file| file.name == name).ok_or("no such structure")?;
+        let structure = file.structure.as_ref()?;
+        let export_path = file.path.with_extension(format.extension());
+        if export_path == file.path {
+            return Err(format!("{} is already {:?}", name, format));
+        }
+        let raw = format.serialize(structure)?;
+        let mut export_file = fs::OpenOptions::new().write(true).create_new(true).open(&export_path).map_err(|err| match err.kind() {
+            std::io::ErrorKind::AlreadyExists => format!("{:?} already exists", export_path),
+            _ => err.to_string(),
+        })?;
+        export_file.write_all(raw.as_bytes()).map_err(|err| err.to_string())?;
+        Ok(export_path)
+    }
+
+    pub fn analyses(&self) -> Vec<(&String, &StructureAnalysis)> {
+        self.files.values().filter_map(|file| Some((&file.name, file.analysis.as_ref()?))).collect()
+    }
+
+    pub fn invalid(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.files.values().filter_map(|file| Some((&file.name, file.structure.as_ref().err()?)))
+    }
+
+    pub fn report_ui(&self, ui: &mut egui::Ui) {
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        for (name, error) in self.invalid() {
+            ui.colored_label(egui::Color32::RED, format!("{}: {}", name, error));
+        }
+        if !self.ignored.is_empty() {
+            ui.weak(format!("Ignored: {}", self.ignored.join(", ")));
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn is_structure_file(path: &Path) -> bool {
+    StructureFormat::from_path(path).is_some()
+}
+
+fn parse_structure(path: &Path, raw: &str) -> Result<SpacecraftStructure, String> {
+    let format = StructureFormat::from_path(path).ok_or("unsupported format")?;
+    let structure = format.parse(raw)?;
+    if !structure.valid() {
+        return Err("structure is not valid".into());
+    }
+    Ok(structure)
+}
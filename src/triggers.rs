@@ -0,0 +1,140 @@
+use std::{collections::HashSet, hash::Hash};
+
+use super::*;
+
+use plugins::SpacecraftState;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TriggerCondition {
+    EnemyNearMyStarBase(f32),
+    MaterialAbove(Material, f32),
+    MaterialBelow(Material, f32),
+    TagCountBelow(String, usize),
+}
+
+impl TriggerCondition {
+    pub fn check(&self, game_data: &GameData) -> bool {
+        match self {
+            TriggerCondition::EnemyNearMyStarBase(distance) => {
+                game_data.my_star_bases().values().any(|star_base| {
+                    game_data.closest_enemy_spacecraft(&star_base.body.position)
+                        .is_some_and(|(_, enemy)| enemy.body.position.distance(star_base.body.position) <= *distance)
+                })
+            }
+            TriggerCondition::MaterialAbove(material, amount) => {
+                game_data.player().materials.get(material).is_some_and(|current| *current >= *amount)
+            }
+            TriggerCondition::MaterialBelow(material, amount) => {
+                game_data.player().materials.get(material).is_some_and(|current| *current < *amount)
+            }
+            TriggerCondition::TagCountBelow(tag, count) => {
+                game_data.my_spacecrafts().values().filter(|spacecraft| spacecraft.tags.contains(tag)).count() < *count
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trigger {
+    pub enabled: bool,
+    pub condition: TriggerCondition,
+    pub tag: String,
+    pub state: SpacecraftState,
+    active: bool,
+    /// Spacecraft already set since the condition started holding
+    applied: HashSet<GameObjectId>,
+}
+
+#[derive(Deserialize)]
+pub struct TriggerV1 {
+    enabled: bool,
+    condition: TriggerCondition,
+    tag: String,
+    state: SpacecraftState,
+    active: bool,
+}
+
+impl From<TriggerV1> for Trigger {
+    fn from(trigger: TriggerV1) -> Self {
+        Self {
+            enabled: trigger.enabled,
+            condition: trigger.condition,
+            tag: trigger.tag,
+            state: trigger.state,
+            active: trigger.active,
+            applied: HashSet::new(),
+        }
+    }
+}
+
+fn newly_tagged<Id: Copy + Eq + Hash>(applied: &mut HashSet<Id>, spacecrafts: &[(Id, &Vec<String>)], tag: &String) -> Vec<Id> {
+    applied.retain(|id| spacecrafts.iter().any(|(x, _)| x == id));
+    let ids = spacecrafts.iter()
+        .filter(|(id, tags)| tags.contains(tag) && !applied.contains(id))
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    applied.extend(&ids);
+    ids
+}
+
+impl Trigger {
+    pub fn new(condition: TriggerCondition, tag: String, state: SpacecraftState) -> Self {
+        Self {
+            enabled: true,
+            condition,
+            tag,
+            state,
+            active: false,
+            applied: HashSet::new(),
+        }
+    }
+
+    pub fn evaluate(&mut self, game_data: &GameData) -> (bool, Vec<GameObjectId>) {
+        let was_active = self.active;
+        self.active = self.enabled && self.condition.check(game_data);
+        if !self.active {
+            self.applied.clear();
+            return (false, vec![]);
+        }
+        let spacecrafts = game_data.my_spacecrafts();
+        let spacecrafts = spacecrafts.iter().map(|(id, spacecraft)| (*id, &spacecraft.tags)).collect::<Vec<_>>();
+        (!was_active, newly_tagged(&mut self.applied, &spacecrafts, &self.tag))
+    }
+
+    pub fn description(&self) -> String {
+        format!("if {:?} then {} -> {:?}", self.condition, self.tag, self.state)
+    }
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Self::new(TriggerCondition::EnemyNearMyStarBase(1000.), String::new(), SpacecraftState::Attack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_each_tagged_spacecraft_once() {
+        let (miner, gunship) = (vec!["miner".to_string()], vec!["gunship".to_string()]);
+        let tag = "miner".to_string();
+        let mut applied = HashSet::new();
+
+        assert_eq!(newly_tagged(&mut applied, &[(1, &miner), (2, &gunship)], &tag), vec![1]);
+        assert!(newly_tagged(&mut applied, &[(1, &miner), (2, &gunship)], &tag).is_empty());
+        assert_eq!(newly_tagged(&mut applied, &[(1, &miner), (3, &miner)], &tag), vec![3]);
+    }
+
+    #[test]
+    fn forgets_spacecraft_that_are_gone() {
+        let miner = vec!["miner".to_string()];
+        let tag = "miner".to_string();
+        let mut applied = HashSet::new();
+
+        newly_tagged(&mut applied, &[(1, &miner), (2, &miner)], &tag);
+        newly_tagged(&mut applied, &[(2, &miner)], &tag);
+        assert_eq!(applied, HashSet::from([2]));
+    }
+}
@@ -1,5 +1,8 @@
 use super::*;
 
+use logging::debug;
+use debug_draw::{Layer, Shape};
+
 pub fn shoot_at(
     (spacecraft_id, spacecraft): (&GameObjectId, &Spacecraft),
     target: GameObjectBody,
@@ -37,6 +40,7 @@ pub fn predictive_shoot_at(
     target: GameObjectBody,
 ) -> Vec<GameCmd> {
     let mut result = vec![];
+    let mut intercepts = vec![];
     for (component_id, component) in &spacecraft.components {
         if let Component::Weapon(weapon) = component {
             let weapon_world_pos = spacecraft
@@ -79,6 +83,9 @@ pub fn predictive_shoot_at(
             }
 
             let result_direction = (relative_pos + relative_vel * t) / t;
+            let intercept = weapon_world_pos + relative_pos + relative_vel * t;
+            intercepts.push(Shape::Line(weapon_world_pos, intercept));
+            intercepts.push(Shape::Circle(intercept, 2.));
             let target_weapon_rotation = result_direction.angle()
                 - (spacecraft.body.rotation + weapon.body.orientation.to_radians());
 
@@ -93,6 +100,7 @@ pub fn predictive_shoot_at(
             set_active(true, &mut result);
         }
     }
+    debug_draw::draw(Layer::Intercepts, spacecraft_id, intercepts);
     result
 }
 
@@ -149,19 +157,25 @@ pub fn impulse_fly_to(
 }
 
 fn optimal_thrust_direction(spacecraft: &Spacecraft) -> (Vec2, f32) {
+    let engines = spacecraft.components.values().filter_map(|component| {
+        if let Component::Engine(engine) = component {
+            Some((Vec2::from_angle(engine.body.orientation.to_radians()), engine.thrust))
+        } else {
+            None
+        }
+    }).collect::<Vec<_>>();
+    optimal_thrust(&engines)
+}
+
+pub fn optimal_thrust(engines: &[(Vec2, f32)]) -> (Vec2, f32) {
     let mut result = Vec2::ONE;
     let bench = |dir: Vec2| -> f32 {
         let mut result = 0.;
-        for component in spacecraft.components.values() {
-            if let Component::Engine(engine) = component {
-                let component_direction = Vec2::from_angle(
-                    engine.body.orientation.to_radians(),
-                );
-                let alignment = component_direction.dot(dir);
-                if alignment > 0. {
-                    let strength = alignment*engine.thrust;
-                    result += strength;
-                }
+        for (component_direction, thrust) in engines {
+            let alignment = component_direction.dot(dir);
+            if alignment > 0. {
+                let strength = alignment*thrust;
+                result += strength;
             }
         }
         result
@@ -309,7 +323,7 @@ pub fn achieve_velocity(
 
     // If the correction is negligible, deactivate engines
     if correction_magnitude < 0.1 {
-        println!("Turning of engines...");
+        debug!(spacecraft: spacecraft_id, "Turning off engines");
         for (component_id, component) in &spacecraft.components {
             if let Component::Engine(engine) = component {
                 if engine.active {
@@ -324,7 +338,7 @@ pub fn achieve_velocity(
         return result;
     }
 
-    dbg!(spacecraft.body.rotation, velocity_correction.angle());
+    debug!(spacecraft: spacecraft_id, "rotation {} correction angle {}", spacecraft.body.rotation, velocity_correction.angle());
 
     let correction_direction = velocity_correction.rotate(-local_thrust_direction).normalize();
 
@@ -335,14 +349,14 @@ pub fn achieve_velocity(
 
     // If the rotation offset is significant, rotate the spacecraft
     if rot_offset.abs() > 0.1 {
-        println!("Rotating to optimal direction...");
+        debug!(spacecraft: spacecraft_id, "Rotating to optimal direction");
         result.extend(rotate_to_direction(
             (spacecraft_id, spacecraft),
             correction_direction,
             1.0, // Rotation speed factor
         ));
     } else {
-        println!("Accelerating...");
+        debug!(spacecraft: spacecraft_id, "Accelerating");
         // Apply thrust to adjust velocity
         for (component_id, component) in &spacecraft.components {
             if let Component::Engine(engine) = component {
@@ -387,7 +401,7 @@ pub fn improved_fly_to(
 
     let relative_velocity = spacecraft.body.velocity - target.velocity;
     let speed_towards_target = relative_velocity.dot(direction_to_target).max(0.0);
-    println!("{speed_towards_target}");
+    debug!(spacecraft: spacecraft_id, "speed towards target {}", speed_towards_target);
 
     let (local_thrust_direction, max_thrust) = optimal_thrust_direction(spacecraft);
 
@@ -405,20 +419,32 @@ pub fn improved_fly_to(
         0.0
     } * distance_safety;
 
-    println!("{} {}", distance_to_target, stopping_distance);
+    debug!(spacecraft: spacecraft_id, "distance {} stopping distance {}", distance_to_target, stopping_distance);
+    debug_draw::draw(Layer::Waypoints, spacecraft_id, vec![
+        Shape::Line(spacecraft.body.position, target.position),
+        Shape::Circle(target.position, stopping_distance.min(distance_to_target)),
+    ]);
 
     if distance_to_target <= stopping_distance && speed_towards_target > 0.0 {
         // Within stopping distance and moving towards the target, start decelerating
-        println!("Matching target velocity...");
+        debug!(spacecraft: spacecraft_id, "Matching target velocity");
+        debug_draw::draw(Layer::Velocities, spacecraft_id, vec![
+            Shape::Velocity(spacecraft.body.position, spacecraft.body.velocity),
+            Shape::Velocity(spacecraft.body.position, target.velocity),
+        ]);
         result.extend(achieve_velocity(
             (spacecraft_id, spacecraft),
             target.velocity, // Match target's velocity
         ));
     } else {
-        println!("Flying to target...");
+        debug!(spacecraft: spacecraft_id, "Flying to target");
         // Outside stopping distance, accelerate towards the target
         let desired_speed = max_thrust;
         let desired_velocity = target.velocity + direction_to_target * desired_speed;
+        debug_draw::draw(Layer::Velocities, spacecraft_id, vec![
+            Shape::Velocity(spacecraft.body.position, spacecraft.body.velocity),
+            Shape::Velocity(spacecraft.body.position, desired_velocity),
+        ]);
         result.extend(achieve_velocity(
             (spacecraft_id, spacecraft),
             desired_velocity,
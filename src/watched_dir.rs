@@ -0,0 +1,73 @@
+use std::{fs, path::{Path, PathBuf}, time::SystemTime};
+
+use super::*;
+
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// New files and files whose modification time changed or can not be read
+    pub changed: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub ignored: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WatchedDir {
+    path: PathBuf,
+    modified: BTreeMap<PathBuf, Option<SystemTime>>,
+}
+
+impl WatchedDir {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            modified: BTreeMap::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn scan(&mut self, filter: impl Fn(&Path) -> bool) -> anyhow::Result<Changes> {
+        fs::create_dir_all(&self.path)?;
+
+        let mut changes = Changes::default();
+        let mut present = vec![];
+        for dir_entry in self.path.read_dir()? {
+            let path = dir_entry?.path();
+            if path.is_dir() {
+                continue;
+            }
+            if !filter(&path) {
+                changes.ignored.push(path);
+                continue;
+            }
+            present.push(path.clone());
+
+            let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+            if let Some(last_modified) = self.modified.get(&path) && last_modified.is_some() && *last_modified == modified {
+                continue;
+            }
+            self.modified.insert(path.clone(), modified);
+            changes.changed.push(path);
+        }
+        self.modified.retain(|path, _| {
+            let kept = present.contains(path);
+            if !kept {
+                changes.removed.push(path.clone());
+            }
+            kept
+        });
+        Ok(changes)
+    }
+
+    pub fn forget(&mut self, path: &Path) {
+        if let Some(modified) = self.modified.get_mut(path) {
+            *modified = None;
+        }
+    }
+}
+
+pub fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|x| x == extension)
+}